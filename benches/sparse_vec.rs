@@ -0,0 +1,73 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use longpage::{next_request_for_view, SparseVec};
+
+/// A vec of `len` with single item blocks every `stride` items
+fn fragmented(len: usize, stride: usize) -> SparseVec<u64> {
+    let mut vec = SparseVec::with_len(len);
+    for start in (0..len).step_by(stride) {
+        vec.insert_vec(start, vec![start as u64]);
+    }
+    vec
+}
+
+fn insert_fragmented(c: &mut Criterion) {
+    let mut group = c.benchmark_group("insert_fragmented");
+    for blocks in [100, 10_000] {
+        group.bench_with_input(
+            BenchmarkId::from_parameter(blocks),
+            &blocks,
+            |b, &blocks| {
+                let vec = fragmented(blocks * 2, 2);
+                b.iter_batched(
+                    || vec.clone(),
+                    |mut vec| {
+                        // fill a gap near the start, shifting nearly every block
+                        vec.insert_vec(1, vec![0]);
+                        vec
+                    },
+                    criterion::BatchSize::SmallInput,
+                )
+            },
+        );
+    }
+    group.finish();
+}
+
+fn iter_range(c: &mut Criterion) {
+    let mut group = c.benchmark_group("iter_range");
+    let vec = fragmented(1_000_000, 10);
+    for window in [1_000, 100_000] {
+        group.bench_with_input(
+            BenchmarkId::from_parameter(window),
+            &window,
+            |b, &window| {
+                b.iter(|| {
+                    vec.iter_range(black_box(500_000..500_000 + window))
+                        .flatten()
+                        .count()
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+fn plan(c: &mut Criterion) {
+    let mut group = c.benchmark_group("next_request_for_view");
+    let patterns = [
+        ("empty", SparseVec::with_len(1_000_000)),
+        ("fragmented", fragmented(1_000_000, 10)),
+        ("loaded", SparseVec::from(vec![0u64; 1_000_000])),
+    ];
+    for (name, vec) in &patterns {
+        for view in [100, 10_000] {
+            group.bench_with_input(BenchmarkId::new(*name, view), &view, |b, &view| {
+                b.iter(|| next_request_for_view(vec, black_box(500_000..500_000 + view)))
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, insert_fragmented, iter_range, plan);
+criterion_main!(benches);
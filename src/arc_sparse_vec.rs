@@ -0,0 +1,257 @@
+use std::{
+    marker::PhantomData,
+    ops::{Index, Range, RangeBounds},
+    sync::Arc,
+};
+
+#[cfg(feature = "bytes")]
+use bytes::Bytes;
+
+use crate::sparse_vec::{
+    block_containing, blocks_in, clamp_range, contiguous_slice, insert_position, Gaps, InsertError,
+    Iter, LoadedRanges, SparseVec,
+};
+
+/// A SparseVec with shared blocks, so that a clone is a cheap snapshot which can be handed to another thread
+/// Blocks are `Arc<[T]>` by default, or with the `bytes` feature can be `Bytes` for byte data
+#[derive(Debug)]
+pub struct ArcSparseVec<T, B = Arc<[T]>> {
+    len: usize,
+    /// Each block starts from an offset within the ArcSparseVec range and proceeds to the end of it's slice
+    blocks: Vec<(usize, B)>,
+    item: PhantomData<T>,
+}
+
+/// An ArcSparseVec of byte blocks which can share the buffers they were received in
+#[cfg(feature = "bytes")]
+pub type BytesSparseVec = ArcSparseVec<u8, Bytes>;
+
+impl<T> ArcSparseVec<T> {
+    pub fn with_len(len: usize) -> Self {
+        ArcSparseVec {
+            len,
+            blocks: vec![],
+            item: PhantomData,
+        }
+    }
+}
+
+#[cfg(feature = "bytes")]
+impl ArcSparseVec<u8, Bytes> {
+    pub fn bytes_with_len(len: usize) -> Self {
+        ArcSparseVec {
+            len,
+            blocks: vec![],
+            item: PhantomData,
+        }
+    }
+}
+
+impl<T, B: AsRef<[T]>> ArcSparseVec<T, B> {
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Get the item at `idx` if it is loaded
+    pub fn get(&self, idx: usize) -> Option<&T> {
+        let (offset, block) = &self.blocks[block_containing(&self.blocks, idx)?];
+        block.as_ref().get(idx - offset)
+    }
+
+    /// Whether the item at `idx` is loaded
+    pub fn contains(&self, idx: usize) -> bool {
+        block_containing(&self.blocks, idx).is_some()
+    }
+
+    /// The items in the range as a slice, if they are all loaded and held by a single block
+    pub fn as_contiguous_slice(&self, idxs: Range<usize>) -> Option<&[T]> {
+        contiguous_slice(&self.blocks, self.len, idxs)
+    }
+
+    pub fn iter_range(&self, idxs: impl RangeBounds<usize>) -> Iter<'_, T, B> {
+        Iter::new(&self.blocks, clamp_range(self.len, idxs))
+    }
+
+    pub fn iter(&self) -> Iter<'_, T, B> {
+        self.iter_range(..)
+    }
+
+    /// Iterate over the loaded blocks as slices along with the range they occupy, clipped to `idxs`
+    pub fn iter_blocks_in(
+        &self,
+        idxs: impl RangeBounds<usize>,
+    ) -> impl DoubleEndedIterator<Item = (Range<usize>, &[T])> + '_ {
+        blocks_in(&self.blocks, clamp_range(self.len, idxs))
+    }
+
+    /// The ranges which are not loaded
+    pub fn gaps(&self) -> Gaps<'_, T, B> {
+        self.gaps_in(..)
+    }
+
+    /// The ranges within `idxs` which are not loaded, clamped to the length
+    pub fn gaps_in(&self, idxs: impl RangeBounds<usize>) -> Gaps<'_, T, B> {
+        Gaps::new(&self.blocks, clamp_range(self.len, idxs))
+    }
+
+    /// Whether every item in the range is loaded, ranges extending past the end are never loaded
+    pub fn is_range_loaded(&self, idxs: Range<usize>) -> bool {
+        idxs.end <= self.len && self.gaps_in(idxs).next().is_none()
+    }
+
+    /// The contiguous ranges which are loaded, adjacent blocks are reported as one range
+    pub fn loaded_ranges(&self) -> LoadedRanges<'_, T, B> {
+        LoadedRanges::new(&self.blocks)
+    }
+
+    /// Insert data into empty space
+    // Panics if space is occupied or the data extends past the end
+    pub fn insert_vec(&mut self, start: usize, vec: Vec<T>)
+    where
+        B: From<Vec<T>>,
+    {
+        if let Err(err) = self.try_insert_vec(start, vec) {
+            panic!("{}", err);
+        }
+    }
+
+    /// Insert data into empty space, or leave the ArcSparseVec unchanged if the space is occupied or the data extends past the end
+    pub fn try_insert_vec(&mut self, start: usize, vec: Vec<T>) -> Result<(), InsertError>
+    where
+        B: From<Vec<T>>,
+    {
+        self.try_insert_shared(start, vec.into())
+    }
+
+    /// Insert a shared block into empty space without copying it
+    // Panics if space is occupied or the data extends past the end
+    pub fn insert_shared(&mut self, start: usize, block: B) {
+        if let Err(err) = self.try_insert_shared(start, block) {
+            panic!("{}", err);
+        }
+    }
+
+    /// Insert a shared block into empty space without copying it, or leave the ArcSparseVec unchanged if the space is occupied or the data extends past the end
+    pub fn try_insert_shared(&mut self, start: usize, block: B) -> Result<(), InsertError> {
+        let insert_pos = insert_position(&self.blocks, self.len, start, block.as_ref().len())?;
+        if !block.as_ref().is_empty() {
+            self.blocks.insert(insert_pos, (start, block));
+        }
+        Ok(())
+    }
+
+    /// Evict all loaded data, keeping the length
+    pub fn clear(&mut self) {
+        self.blocks.clear();
+    }
+}
+
+impl<T, B: Clone> Clone for ArcSparseVec<T, B> {
+    /// Share the blocks of this ArcSparseVec, which is O(blocks) rather than O(items)
+    fn clone(&self) -> Self {
+        ArcSparseVec {
+            len: self.len,
+            blocks: self.blocks.clone(),
+            item: PhantomData,
+        }
+    }
+}
+
+impl<T> From<SparseVec<T>> for ArcSparseVec<T> {
+    fn from(vec: SparseVec<T>) -> Self {
+        ArcSparseVec {
+            item: PhantomData,
+            len: vec.len(),
+            blocks: vec
+                .into_blocks()
+                .into_iter()
+                .map(|(offset, block)| (offset, block.into()))
+                .collect(),
+        }
+    }
+}
+
+impl<T, B: AsRef<[T]>> Index<usize> for ArcSparseVec<T, B> {
+    type Output = T;
+
+    // Panics if the item is not loaded
+    fn index(&self, idx: usize) -> &T {
+        self.get(idx)
+            .unwrap_or_else(|| panic!("Index {} is not loaded", idx))
+    }
+}
+
+#[test]
+fn clone_shares_blocks() {
+    let mut vec = ArcSparseVec::with_len(10);
+    vec.insert_vec(2, vec![3, 4]);
+    let snapshot = vec.clone();
+    vec.insert_vec(6, vec![7]);
+    assert!(Arc::ptr_eq(&vec.blocks[0].1, &snapshot.blocks[0].1));
+    assert_eq!(snapshot.loaded_ranges().collect::<Vec<_>>(), vec![2..4]);
+    assert_eq!(vec.loaded_ranges().collect::<Vec<_>>(), vec![2..4, 6..7]);
+}
+
+#[test]
+fn read_api_matches_sparse_vec() {
+    let mut sparse = SparseVec::with_len(10);
+    sparse.insert_vec(1, vec![2, 3]);
+    sparse.insert_vec(3, vec![4]);
+    sparse.insert_vec(7, vec![8, 9]);
+    let shared = ArcSparseVec::from(sparse.clone());
+    assert_eq!(
+        shared.iter().collect::<Vec<_>>(),
+        sparse.iter().collect::<Vec<_>>()
+    );
+    assert_eq!(
+        shared.iter_range(2..8).rev().collect::<Vec<_>>(),
+        sparse.iter_range(2..8).rev().collect::<Vec<_>>()
+    );
+    assert_eq!(
+        shared.gaps().collect::<Vec<_>>(),
+        sparse.gaps().collect::<Vec<_>>()
+    );
+    assert_eq!(
+        shared.iter_blocks_in(2..8).collect::<Vec<_>>(),
+        sparse.iter_blocks_in(2..8).collect::<Vec<_>>()
+    );
+    assert_eq!(shared[7], 8);
+    assert_eq!(shared.as_contiguous_slice(7..9), Some(&[8, 9][..]));
+    assert!(shared.is_range_loaded(1..4));
+    assert_eq!(
+        shared.clone().try_insert_vec(0, vec![1, 2]),
+        Err(InsertError::Overlap(1..3))
+    );
+}
+
+#[test]
+fn insert_shared_without_copying() {
+    let payload: Arc<[u32]> = vec![1, 2, 3].into();
+    let mut vec = ArcSparseVec::with_len(10);
+    vec.insert_shared(4, payload.clone());
+    assert!(Arc::ptr_eq(&vec.blocks[0].1, &payload));
+    assert_eq!(vec.get(5), Some(&2));
+    assert_eq!(
+        vec.try_insert_shared(5, payload),
+        Err(InsertError::Overlap(4..7))
+    );
+}
+
+#[cfg(feature = "bytes")]
+#[test]
+fn insert_bytes_without_copying() {
+    let payload = Bytes::from_static(b"hello world");
+    let mut vec = BytesSparseVec::bytes_with_len(100);
+    vec.insert_shared(10, payload.slice(6..));
+    vec.insert_vec(0, b"hello".to_vec());
+    assert_eq!(vec.as_contiguous_slice(10..15), Some(&b"world"[..]));
+    assert_eq!(
+        vec.as_contiguous_slice(10..15).unwrap().as_ptr(),
+        payload[6..].as_ptr()
+    );
+    assert_eq!(vec.loaded_ranges().collect::<Vec<_>>(), vec![0..5, 10..15]);
+}
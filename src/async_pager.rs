@@ -0,0 +1,267 @@
+use std::{collections::HashMap, error::Error, fmt, ops::Range, sync::Arc};
+
+use tokio::task::{AbortHandle, Id, JoinSet};
+
+use crate::{
+    pager::Pager,
+    planner::PrefetchConfig,
+    source::PageSource,
+    sparse_vec::{Iter, SparseVec},
+};
+
+/// Why the viewport couldn't be loaded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LoadError<E> {
+    /// A fetch failed
+    Fetch(E),
+    /// Nothing is in flight and nothing more can be requested for these missing ranges, such as when the config skips small gaps
+    Unrequestable(Vec<Range<usize>>),
+}
+
+impl<E: fmt::Display> fmt::Display for LoadError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Fetch(err) => write!(f, "Fetch failed: {}", err),
+            LoadError::Unrequestable(missing) => {
+                write!(f, "Ranges {:?} in view can't be requested", missing)
+            }
+        }
+    }
+}
+
+impl<E: fmt::Debug + fmt::Display> Error for LoadError<E> {}
+
+/// A `Pager` which fetches from a `PageSource` on tokio tasks as the viewport moves
+pub struct AsyncPager<T, S: PageSource<T>> {
+    source: Arc<S>,
+    pager: Pager<T>,
+    fetches: JoinSet<Result<Vec<T>, S::Error>>,
+    /// The range each fetch is for and how to abort it
+    ranges: HashMap<Id, (Range<usize>, AbortHandle)>,
+}
+
+impl<T, S> AsyncPager<T, S>
+where
+    T: Send + 'static,
+    S: PageSource<T> + Send + Sync + 'static,
+    S::Error: Send + 'static,
+{
+    /// Page through the data from `source`, asking it for the length first
+    pub async fn new(source: S, config: PrefetchConfig) -> Self {
+        let data = match source.len().await {
+            Some(len) => SparseVec::with_len(len),
+            None => SparseVec::with_unknown_len(),
+        };
+        AsyncPager {
+            source: Arc::new(source),
+            pager: Pager::new(data, config),
+            fetches: JoinSet::new(),
+            ranges: HashMap::new(),
+        }
+    }
+
+    /// Allow up to `max_in_flight` fetches at once
+    // Panics if `max_in_flight` is 0
+    pub fn with_max_in_flight(self, max_in_flight: usize) -> Self {
+        AsyncPager {
+            pager: self.pager.with_max_in_flight(max_in_flight),
+            ..self
+        }
+    }
+
+    /// Move the viewport, aborting fetches which are no longer needed and starting those which are
+    // Panics if called outside of a tokio runtime
+    pub fn set_viewport(&mut self, viewport: Range<usize>) {
+        self.pager.set_viewport(viewport);
+        for range in self.pager.cancellable() {
+            self.ranges.retain(|_, (in_flight, abort)| {
+                if *in_flight == range {
+                    abort.abort();
+                    false
+                } else {
+                    true
+                }
+            });
+            self.pager.fail(&range);
+        }
+        self.spawn_fetches();
+    }
+
+    /// Wait for the next fetch to finish and load it, returning the range loaded, None if no fetches are in flight
+    /// A failed fetch isn't retried until the viewport is set again or `viewport_loaded` is awaited
+    pub async fn next_response(&mut self) -> Option<Result<Range<usize>, S::Error>> {
+        loop {
+            let (id, result) = match self.fetches.join_next_with_id().await? {
+                Ok(finished) => finished,
+                Err(err) if err.is_cancelled() => continue,
+                Err(err) => std::panic::resume_unwind(err.into_panic()),
+            };
+            // a fetch which finished as it was aborted is no longer tracked
+            let (range, _) = match self.ranges.remove(&id) {
+                Some(fetch) => fetch,
+                None => continue,
+            };
+            return Some(match result {
+                Ok(items) => {
+                    self.pager.complete(range.clone(), items);
+                    self.spawn_fetches();
+                    Ok(range)
+                }
+                Err(err) => {
+                    self.pager.fail(&range);
+                    Err(err)
+                }
+            });
+        }
+    }
+
+    /// Wait until every item in the viewport is loaded, or a fetch fails or the rest of the viewport can't be requested
+    pub async fn viewport_loaded(&mut self) -> Result<(), LoadError<S::Error>> {
+        while !self.pager.is_viewport_loaded() {
+            self.spawn_fetches();
+            match self.next_response().await {
+                Some(Ok(_)) => {}
+                Some(Err(err)) => return Err(LoadError::Fetch(err)),
+                None => break,
+            }
+        }
+        let missing = self.pager.missing_in_viewport();
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(LoadError::Unrequestable(missing))
+        }
+    }
+
+    fn spawn_fetches(&mut self) {
+        while let Some(range) = self.pager.poll_request() {
+            let source = self.source.clone();
+            let fetch_range = range.clone();
+            let abort = self
+                .fetches
+                .spawn(async move { source.fetch(fetch_range).await });
+            self.ranges.insert(abort.id(), (range, abort));
+        }
+    }
+
+    pub fn viewport(&self) -> &Range<usize> {
+        self.pager.viewport()
+    }
+
+    /// The items in the viewport, None for those which aren't loaded yet
+    pub fn iter_viewport(&self) -> Iter<'_, T> {
+        self.pager.iter_viewport()
+    }
+
+    pub fn get(&self, idx: usize) -> Option<&T> {
+        self.pager.get(idx)
+    }
+
+    pub fn pager(&self) -> &Pager<T> {
+        &self.pager
+    }
+}
+
+#[cfg(test)]
+struct TestSource {
+    items: Vec<usize>,
+    /// Fetches which include this index fail
+    fail_at: Option<usize>,
+    /// Fetches which start at or after this index never finish
+    hang_from: usize,
+}
+
+#[cfg(test)]
+impl PageSource<usize> for TestSource {
+    type Error = String;
+
+    async fn fetch(&self, range: Range<usize>) -> Result<Vec<usize>, String> {
+        if range.start >= self.hang_from {
+            std::future::pending::<()>().await;
+        }
+        if self.fail_at.is_some_and(|idx| range.contains(&idx)) {
+            return Err(format!("failed to fetch {:?}", range));
+        }
+        Ok(self.items[range.start.min(self.items.len())..range.end.min(self.items.len())].to_vec())
+    }
+
+    async fn len(&self) -> Option<usize> {
+        Some(self.items.len())
+    }
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn load_viewport() {
+    let source = TestSource {
+        items: (0..100).collect(),
+        fail_at: None,
+        hang_from: usize::MAX,
+    };
+    let mut pager = AsyncPager::new(source, PrefetchConfig::default()).await;
+    pager.set_viewport(10..20);
+    pager.viewport_loaded().await.unwrap();
+    assert_eq!(pager.get(10), Some(&10));
+    assert!(pager.iter_viewport().all(|item| item.is_some()));
+    assert_eq!(pager.next_response().await, None);
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn abort_fetches_scrolled_away_from() {
+    let source = TestSource {
+        items: (0..1000).collect(),
+        fail_at: None,
+        hang_from: 500,
+    };
+    let mut pager = AsyncPager::new(source, PrefetchConfig::default()).await;
+    pager.set_viewport(600..620);
+    assert_eq!(pager.pager().in_flight().len(), 1);
+    pager.set_viewport(10..20);
+    pager.viewport_loaded().await.unwrap();
+    assert!(pager.pager().in_flight().is_empty());
+    assert_eq!(pager.get(15), Some(&15));
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn report_failed_fetch() {
+    let source = TestSource {
+        items: (0..100).collect(),
+        fail_at: Some(12),
+        hang_from: usize::MAX,
+    };
+    let mut pager = AsyncPager::new(source, PrefetchConfig::default()).await;
+    pager.set_viewport(10..20);
+    assert_eq!(
+        pager.viewport_loaded().await,
+        Err(LoadError::Fetch("failed to fetch 5..25".to_string()))
+    );
+    assert!(pager.pager().in_flight().is_empty());
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn report_viewport_which_cant_be_requested() {
+    use crate::planner::SmallGaps;
+
+    let source = TestSource {
+        items: (0..100).collect(),
+        fail_at: None,
+        hang_from: usize::MAX,
+    };
+    let config = PrefetchConfig {
+        min_request: Some(50),
+        small_gaps: SmallGaps::Skip,
+        ..PrefetchConfig::default()
+    };
+    let mut pager = AsyncPager::new(source, config).await;
+    pager.set_viewport(10..20);
+    assert!(pager.pager().in_flight().is_empty());
+    let err = pager.viewport_loaded().await.unwrap_err();
+    assert!(
+        matches!(&err, LoadError::Unrequestable(missing) if missing.len() == 1 && missing[0] == (10..20)),
+        "{}",
+        err
+    );
+}
@@ -0,0 +1,163 @@
+use std::ops::Range;
+
+use crate::{
+    next_request_for_view,
+    sparse_vec::{InsertError, Iter, SparseVec},
+};
+
+/// A SparseVec indexed by signed positions, which can grow in both directions without shifting its data
+// Positions are stored at indices of an inner SparseVec spanning all of `usize`, with position 0 in the middle
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BidirectionalSparseVec<T> {
+    vec: SparseVec<T>,
+}
+
+/// Order preserving map from positions to indices of the inner SparseVec
+fn to_index(position: isize) -> usize {
+    (position as usize) ^ (1 << (usize::BITS - 1))
+}
+
+/// Order preserving map from indices of the inner SparseVec to positions
+fn to_position(idx: usize) -> isize {
+    (idx ^ (1 << (usize::BITS - 1))) as isize
+}
+
+fn to_indices(positions: Range<isize>) -> Range<usize> {
+    to_index(positions.start)..to_index(positions.end)
+}
+
+fn to_positions(idxs: Range<usize>) -> Range<isize> {
+    to_position(idxs.start)..to_position(idxs.end)
+}
+
+impl<T> BidirectionalSparseVec<T> {
+    pub fn new() -> Self {
+        BidirectionalSparseVec {
+            vec: SparseVec::with_len(usize::MAX),
+        }
+    }
+
+    /// Get the item at `position` if it is loaded
+    pub fn get(&self, position: isize) -> Option<&T> {
+        self.vec.get(to_index(position))
+    }
+
+    /// Get the item at `position` mutably if it is loaded
+    pub fn get_mut(&mut self, position: isize) -> Option<&mut T> {
+        self.vec.get_mut(to_index(position))
+    }
+
+    /// Whether the item at `position` is loaded
+    pub fn contains(&self, position: isize) -> bool {
+        self.vec.contains(to_index(position))
+    }
+
+    pub fn iter_range(&self, positions: Range<isize>) -> Iter<'_, T> {
+        self.vec.iter_range(to_indices(positions))
+    }
+
+    /// Iterate over only the loaded items along with their positions
+    pub fn iter_present(&self) -> impl DoubleEndedIterator<Item = (isize, &T)> + '_ {
+        self.vec
+            .iter_present()
+            .map(|(idx, item)| (to_position(idx), item))
+    }
+
+    /// The ranges within `positions` which are not loaded
+    pub fn gaps_in(&self, positions: Range<isize>) -> impl Iterator<Item = Range<isize>> + '_ {
+        self.vec.gaps_in(to_indices(positions)).map(to_positions)
+    }
+
+    /// The contiguous ranges which are loaded
+    pub fn loaded_ranges(&self) -> impl Iterator<Item = Range<isize>> + '_ {
+        self.vec.loaded_ranges().map(to_positions)
+    }
+
+    /// Insert data into empty space
+    // Panics if space is occupied
+    pub fn insert_vec(&mut self, start: isize, vec: Vec<T>) {
+        if let Err(err) = self.try_insert_vec(start, vec) {
+            panic!("{}", err);
+        }
+    }
+
+    /// Insert data into empty space, or leave it unchanged if the space is occupied
+    pub fn try_insert_vec(&mut self, start: isize, vec: Vec<T>) -> Result<(), InsertError<isize>> {
+        self.vec
+            .try_insert_vec(to_index(start), vec)
+            .map_err(|err| err.map_index(to_position))
+    }
+
+    /// Evict any loaded data in the range
+    pub fn remove_range(&mut self, positions: Range<isize>) {
+        self.vec.remove_range(to_indices(positions));
+    }
+
+    /// Evict all loaded data outside the range
+    pub fn evict_outside(&mut self, keep: Range<isize>) {
+        self.vec.evict_outside(to_indices(keep));
+    }
+
+    /// The range of positions which should be requested next for the view, as `next_request_for_view`
+    pub fn next_request_for_view(&self, in_view: Range<isize>) -> Option<Range<isize>> {
+        next_request_for_view(&self.vec, to_indices(in_view)).map(to_positions)
+    }
+}
+
+impl<T> Default for BidirectionalSparseVec<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[test]
+fn position_mapping_preserves_order() {
+    let positions = [isize::MIN, -100, -1, 0, 1, 100, isize::MAX];
+    for pair in positions.windows(2) {
+        assert!(to_index(pair[0]) < to_index(pair[1]));
+    }
+    for position in positions.iter() {
+        assert_eq!(to_position(to_index(*position)), *position);
+    }
+}
+
+#[test]
+fn prepend_before_zero() {
+    let mut vec = BidirectionalSparseVec::new();
+    vec.insert_vec(0, vec![0, 1, 2]);
+    vec.insert_vec(-3, vec![-3, -2, -1]);
+    vec.insert_vec(-10, vec![-10]);
+    assert_eq!(
+        vec.iter_range(-4..4).collect::<Vec<_>>(),
+        vec![
+            None,
+            Some(&-3),
+            Some(&-2),
+            Some(&-1),
+            Some(&0),
+            Some(&1),
+            Some(&2),
+            None
+        ]
+    );
+    assert_eq!(vec.get(-10), Some(&-10));
+    assert_eq!(
+        vec.loaded_ranges().collect::<Vec<_>>(),
+        vec![-10..-9, -3..3]
+    );
+    assert_eq!(
+        vec.gaps_in(-12..0).collect::<Vec<_>>(),
+        vec![-12..-10, -9..-3]
+    );
+    assert_eq!(
+        vec.try_insert_vec(-4, vec![-4, -3]),
+        Err(InsertError::Overlap(-3..0))
+    );
+}
+
+#[test]
+fn plan_before_zero() {
+    let mut vec = BidirectionalSparseVec::new();
+    vec.insert_vec(0, (0..10).collect());
+    assert_eq!(vec.next_request_for_view(-5..5), Some(-10..0));
+}
@@ -0,0 +1,197 @@
+use std::{collections::BTreeSet, ops::Range};
+
+use crate::planner::Request;
+
+/// Page boundaries discovered from a server which only serves fixed pages, such as a cursor API, so that requests can be aligned to them
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PageBoundaries {
+    boundaries: BTreeSet<usize>,
+}
+
+impl PageBoundaries {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record that a page starts at `idx`
+    pub fn insert(&mut self, idx: usize) {
+        self.boundaries.insert(idx);
+    }
+
+    /// Record the range of a page which has been received
+    pub fn record_page(&mut self, page: Range<usize>) {
+        self.boundaries.insert(page.start);
+        self.boundaries.insert(page.end);
+    }
+
+    /// The known boundaries in order
+    pub fn boundaries(&self) -> impl Iterator<Item = usize> + '_ {
+        self.boundaries.iter().copied()
+    }
+
+    /// The page containing the item at `idx`, None if a boundary either side of it isn't known
+    pub fn page_containing(&self, idx: usize) -> Option<Range<usize>> {
+        let start = *self.boundaries.range(..=idx).next_back()?;
+        let end = *self.boundaries.range(idx.checked_add(1)?..).next()?;
+        Some(start..end)
+    }
+
+    /// The range extended out to the nearest known boundaries and split into a range per page, ends without a known boundary beyond them are left as they are
+    pub fn align(&self, range: &Range<usize>) -> Vec<Range<usize>> {
+        if range.is_empty() {
+            return vec![];
+        }
+        let start = self
+            .boundaries
+            .range(..=range.start)
+            .next_back()
+            .copied()
+            .unwrap_or(range.start);
+        let end = self
+            .boundaries
+            .range(range.end..)
+            .next()
+            .copied()
+            .unwrap_or(range.end);
+        let mut pages = vec![];
+        let mut page_start = start;
+        for &boundary in self.boundaries.range(start.saturating_add(1)..end) {
+            pages.push(page_start..boundary);
+            page_start = boundary;
+        }
+        pages.push(page_start..end);
+        pages
+    }
+
+    /// Align each request, keeping its priority, the parts of pages covered by an earlier request are dropped and the earlier request takes the more urgent priority
+    pub fn align_requests(&self, requests: Vec<Request>) -> Vec<Request> {
+        let mut aligned: Vec<Request> = vec![];
+        for request in requests {
+            for page in self.align(&request.range) {
+                let mut uncovered = vec![page];
+                for earlier in &mut aligned {
+                    let overlaps = uncovered.iter().any(|piece| {
+                        piece.start < earlier.range.end && earlier.range.start < piece.end
+                    });
+                    if !overlaps {
+                        continue;
+                    }
+                    earlier.priority = earlier.priority.min(request.priority);
+                    uncovered = uncovered
+                        .into_iter()
+                        .flat_map(|piece| {
+                            vec![
+                                piece.start..piece.end.min(earlier.range.start),
+                                piece.start.max(earlier.range.end)..piece.end,
+                            ]
+                        })
+                        .filter(|piece| !piece.is_empty())
+                        .collect();
+                }
+                aligned.extend(uncovered.into_iter().map(|range| Request {
+                    range,
+                    priority: request.priority,
+                }));
+            }
+        }
+        aligned
+    }
+}
+
+#[test]
+fn align_to_known_pages() {
+    let mut pages = PageBoundaries::new();
+    assert_eq!(pages.align(&(5..15)), vec![5..15]);
+    pages.record_page(0..10);
+    pages.record_page(10..25);
+    assert_eq!(pages.page_containing(12), Some(10..25));
+    assert_eq!(pages.page_containing(30), None);
+    assert_eq!(pages.align(&(5..15)), vec![0..10, 10..25]);
+    assert_eq!(pages.align(&(20..40)), vec![10..25, 25..40]);
+    assert_eq!(pages.align(&(10..25)), vec![10..25]);
+    assert_eq!(pages.align(&(12..12)), vec![]);
+}
+
+#[test]
+fn boundaries_at_end_of_indices() {
+    let mut pages = PageBoundaries::new();
+    pages.record_page(usize::MAX - 10..usize::MAX);
+    assert_eq!(
+        pages.page_containing(usize::MAX - 1),
+        Some(usize::MAX - 10..usize::MAX)
+    );
+    assert_eq!(pages.page_containing(usize::MAX), None);
+    assert_eq!(
+        pages.align(&(usize::MAX - 5..usize::MAX)),
+        vec![usize::MAX - 10..usize::MAX]
+    );
+    pages.insert(usize::MAX - 1);
+    assert_eq!(
+        pages.align(&(usize::MAX - 1..usize::MAX)),
+        vec![usize::MAX - 1..usize::MAX]
+    );
+}
+
+#[test]
+fn align_planned_requests() {
+    use crate::{
+        planner::{tagged_requests_for_view, PrefetchConfig, Priority},
+        SparseVec,
+    };
+
+    let mut p = SparseVec::<u8>::with_len(100);
+    p.insert_vec(0, vec![0; 10]);
+    p.insert_vec(14, vec![0; 2]);
+    let mut pages = PageBoundaries::new();
+    pages.record_page(0..10);
+    pages.insert(20);
+    let requests = tagged_requests_for_view(&p, 5..15, &PrefetchConfig::items(10));
+    assert_eq!(
+        pages.align_requests(requests),
+        vec![
+            Request {
+                range: 10..20,
+                priority: Priority::Visible
+            },
+            Request {
+                range: 20..25,
+                priority: Priority::Prefetch
+            },
+        ]
+    );
+}
+
+#[test]
+fn requests_sharing_a_page_with_unknown_end() {
+    use crate::planner::Priority;
+
+    let mut pages = PageBoundaries::new();
+    pages.insert(25);
+    let requests = vec![
+        Request {
+            range: 20..28,
+            priority: Priority::Prefetch,
+        },
+        Request {
+            range: 30..50,
+            priority: Priority::Visible,
+        },
+    ];
+    assert_eq!(
+        pages.align_requests(requests),
+        vec![
+            Request {
+                range: 20..25,
+                priority: Priority::Prefetch
+            },
+            Request {
+                range: 25..28,
+                priority: Priority::Visible
+            },
+            Request {
+                range: 28..50,
+                priority: Priority::Visible
+            },
+        ]
+    );
+}
@@ -0,0 +1,254 @@
+use std::{
+    collections::BTreeMap,
+    iter,
+    ops::{Range, RangeBounds},
+};
+
+use crate::sparse_vec::{clamp_range, InsertError, SparseVec};
+
+/// A sparse vec which keeps its blocks in a BTreeMap keyed by offset, so that inserting and removing blocks stays O(log blocks) when loading is very fine grained
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BTreeSparseVec<T> {
+    len: usize,
+    /// Each block starts from its key and proceeds to the end of it's Vec, blocks don't overlap and aren't empty
+    blocks: BTreeMap<usize, Vec<T>>,
+}
+
+impl<T> BTreeSparseVec<T> {
+    pub fn with_len(len: usize) -> Self {
+        BTreeSparseVec {
+            len,
+            blocks: BTreeMap::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Get the item at `idx` if it is loaded
+    pub fn get(&self, idx: usize) -> Option<&T> {
+        let (offset, block) = self.blocks.range(..=idx).next_back()?;
+        block.get(idx - offset)
+    }
+
+    /// Get the item at `idx` mutably if it is loaded
+    pub fn get_mut(&mut self, idx: usize) -> Option<&mut T> {
+        let (offset, block) = self.blocks.range_mut(..=idx).next_back()?;
+        block.get_mut(idx - *offset)
+    }
+
+    /// Whether the item at `idx` is loaded
+    pub fn contains(&self, idx: usize) -> bool {
+        self.get(idx).is_some()
+    }
+
+    /// Iterate over the items in the range, clamped to the length, with `None` for those which are not loaded
+    pub fn iter_range(
+        &self,
+        idxs: impl RangeBounds<usize>,
+    ) -> impl Iterator<Item = Option<&T>> + '_ {
+        let idxs = clamp_range(self.len, idxs);
+        let mut position = idxs.start;
+        self.iter_blocks_in(idxs.clone())
+            .chain(iter::once((idxs.end..idxs.end, &[][..])))
+            .flat_map(move |(range, block)| {
+                let gap = range.start - position;
+                position = range.end;
+                iter::repeat_n(None, gap).chain(block.iter().map(Some))
+            })
+    }
+
+    /// Iterate over the loaded blocks as slices along with the range they occupy, clipped to `idxs`
+    pub fn iter_blocks_in(
+        &self,
+        idxs: impl RangeBounds<usize>,
+    ) -> impl Iterator<Item = (Range<usize>, &[T])> + '_ {
+        let idxs = clamp_range(self.len, idxs);
+        self.blocks_overlapping(idxs.clone())
+            .map(move |(offset, block)| {
+                let start = idxs.start.max(*offset);
+                let end = idxs.end.min(offset + block.len());
+                (start..end, &block[(start - offset)..(end - offset)])
+            })
+    }
+
+    /// The ranges within `idxs` which are not loaded, clamped to the length
+    pub fn gaps_in(
+        &self,
+        idxs: impl RangeBounds<usize>,
+    ) -> impl Iterator<Item = Range<usize>> + '_ {
+        let idxs = clamp_range(self.len, idxs);
+        let mut position = idxs.start;
+        self.iter_blocks_in(idxs.clone())
+            .map(|(range, _)| range)
+            .chain(iter::once(idxs.end..idxs.end))
+            .filter_map(move |range| {
+                let gap = position..range.start;
+                position = range.end;
+                Some(gap).filter(|gap| !gap.is_empty())
+            })
+    }
+
+    /// Whether every item in the range is loaded, ranges extending past the end are never loaded
+    pub fn is_range_loaded(&self, idxs: Range<usize>) -> bool {
+        idxs.end <= self.len && self.gaps_in(idxs).next().is_none()
+    }
+
+    /// The contiguous ranges which are loaded, adjacent blocks are reported as one range
+    pub fn loaded_ranges(&self) -> Vec<Range<usize>> {
+        let mut ranges: Vec<Range<usize>> = vec![];
+        for (offset, block) in &self.blocks {
+            match ranges.last_mut() {
+                Some(range) if range.end == *offset => range.end += block.len(),
+                _ => ranges.push(*offset..(offset + block.len())),
+            }
+        }
+        ranges
+    }
+
+    /// Insert data into empty space
+    // Panics if space is occupied or the data extends past the end
+    pub fn insert_vec(&mut self, start: usize, vec: Vec<T>) {
+        if let Err(err) = self.try_insert_vec(start, vec) {
+            panic!("{}", err);
+        }
+    }
+
+    /// Insert data into empty space, or leave the BTreeSparseVec unchanged if the space is occupied or the data extends past the end
+    pub fn try_insert_vec(&mut self, start: usize, vec: Vec<T>) -> Result<(), InsertError> {
+        let end = start
+            .checked_add(vec.len())
+            .filter(|end| *end <= self.len)
+            .ok_or(InsertError::OutOfBounds {
+                start,
+                len: vec.len(),
+            })?;
+        if vec.is_empty() {
+            return Ok(());
+        }
+        if let Some((offset, block)) = self.blocks_overlapping(start..end).next() {
+            return Err(InsertError::Overlap(*offset..(offset + block.len())));
+        }
+        self.blocks.insert(start, vec);
+        Ok(())
+    }
+
+    /// Evict any loaded data in the range, splitting blocks which straddle its ends
+    pub fn remove_range(&mut self, range: Range<usize>) {
+        if range.is_empty() {
+            return;
+        }
+        let offsets: Vec<usize> = self
+            .blocks_overlapping(range.clone())
+            .map(|(offset, _)| *offset)
+            .collect();
+        for offset in offsets {
+            let mut block = self.blocks.remove(&offset).unwrap();
+            if range.end < offset + block.len() {
+                let tail = block.split_off(range.end - offset);
+                self.blocks.insert(range.end, tail);
+            }
+            if offset < range.start {
+                block.truncate(range.start - offset);
+                self.blocks.insert(offset, block);
+            }
+        }
+    }
+
+    /// Evict all loaded data, keeping the length
+    pub fn clear(&mut self) {
+        self.blocks.clear();
+    }
+
+    /// The blocks which hold any items in the range, in order
+    fn blocks_overlapping(&self, idxs: Range<usize>) -> impl Iterator<Item = (&usize, &Vec<T>)> {
+        let before = self
+            .blocks
+            .range(..idxs.start)
+            .next_back()
+            .filter(|(offset, block)| *offset + block.len() > idxs.start);
+        before.into_iter().chain(self.blocks.range(idxs))
+    }
+}
+
+impl<T> From<SparseVec<T>> for BTreeSparseVec<T> {
+    fn from(vec: SparseVec<T>) -> Self {
+        BTreeSparseVec {
+            len: vec.len(),
+            blocks: vec
+                .into_blocks()
+                .into_iter()
+                .filter(|(_, block)| !block.is_empty())
+                .collect(),
+        }
+    }
+}
+
+impl<T> From<BTreeSparseVec<T>> for SparseVec<T> {
+    fn from(vec: BTreeSparseVec<T>) -> Self {
+        SparseVec::from_blocks(vec.len, vec.blocks)
+            .expect("BTreeSparseVec blocks are within the length and don't overlap")
+    }
+}
+
+#[test]
+fn matches_sparse_vec() {
+    let mut sparse = SparseVec::with_len(50);
+    let mut btree = BTreeSparseVec::with_len(50);
+    for (start, len) in [(10, 5), (0, 3), (15, 2), (30, 10), (45, 5)] {
+        let items: Vec<usize> = (start..start + len).collect();
+        sparse.insert_vec(start, items.clone());
+        btree.insert_vec(start, items);
+    }
+    for removed in [12..13, 29..31, 38..46] {
+        sparse.remove_range(removed.clone());
+        btree.remove_range(removed);
+    }
+    assert_eq!(
+        btree.iter_range(..).collect::<Vec<_>>(),
+        sparse.iter().collect::<Vec<_>>()
+    );
+    assert_eq!(
+        btree.iter_range(11..33).collect::<Vec<_>>(),
+        sparse.iter_range(11..33).collect::<Vec<_>>()
+    );
+    assert_eq!(
+        btree.gaps_in(..).collect::<Vec<_>>(),
+        sparse.gaps().collect::<Vec<_>>()
+    );
+    assert_eq!(
+        btree.loaded_ranges(),
+        sparse.loaded_ranges().collect::<Vec<_>>()
+    );
+    assert_eq!(SparseVec::from(btree.clone()), sparse);
+    assert_eq!(BTreeSparseVec::from(sparse), btree);
+}
+
+#[test]
+fn insert_errors() {
+    let mut vec = BTreeSparseVec::with_len(10);
+    vec.insert_vec(2, vec![0; 4]);
+    assert_eq!(
+        vec.try_insert_vec(5, vec![0; 2]),
+        Err(InsertError::Overlap(2..6))
+    );
+    assert_eq!(
+        vec.try_insert_vec(0, vec![0; 3]),
+        Err(InsertError::Overlap(2..6))
+    );
+    assert_eq!(
+        vec.try_insert_vec(8, vec![0; 3]),
+        Err(InsertError::OutOfBounds { start: 8, len: 3 })
+    );
+    assert_eq!(vec.try_insert_vec(6, vec![1; 4]), Ok(()));
+    assert_eq!(vec.get(9), Some(&1));
+    if let Some(item) = vec.get_mut(2) {
+        *item = 3;
+    }
+    assert_eq!(vec.get(2), Some(&3));
+}
@@ -0,0 +1,237 @@
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::store::BlockStore;
+
+/// Distinguishes the directories of stores created by this process
+static NEXT_STORE: AtomicUsize = AtomicUsize::new(0);
+
+/// A BlockStore which keeps a limited number of blocks in memory, spilling the least recently used blocks to files in a directory and reading them back when they are accessed
+/// A block which can't be written stays in memory and one which can't be read back is reported as missing, the error is kept for `take_error`
+#[derive(Debug)]
+pub struct DiskStore<T> {
+    /// A directory of this store's own, so that stores sharing a parent directory don't overwrite each other's blocks
+    dir: PathBuf,
+    /// Maximum number of blocks to keep in memory
+    resident_blocks: usize,
+    /// Incremented on each access
+    clock: u64,
+    /// Blocks in memory by offset, with the clock at their last access
+    resident: HashMap<usize, (Vec<T>, u64)>,
+    /// Offsets of the blocks which have been spilled to files
+    spilled: Vec<usize>,
+    /// The most recent error writing or reading a block
+    error: Option<io::Error>,
+}
+
+impl<T: Serialize + DeserializeOwned> DiskStore<T> {
+    /// Spill blocks to files in a new directory within `parent`, which is created if needed, when there are more than `resident_blocks` in memory
+    pub fn new(parent: impl AsRef<Path>, resident_blocks: usize) -> io::Result<Self> {
+        let dir = parent.as_ref().join(format!(
+            "longpage-{}-{}",
+            process::id(),
+            NEXT_STORE.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::create_dir_all(&dir)?;
+        Ok(DiskStore {
+            dir,
+            resident_blocks,
+            clock: 0,
+            resident: HashMap::new(),
+            spilled: vec![],
+            error: None,
+        })
+    }
+
+    /// Spill least recently used blocks other than `keep` until within the resident limit, a block which fails to be written is kept in memory
+    fn spill_to_limit(&mut self, keep: usize) -> io::Result<()> {
+        while self.resident.len() > self.resident_blocks {
+            let lru = self
+                .resident
+                .iter()
+                .filter(|(offset, _)| **offset != keep)
+                .min_by_key(|(_, (_, accessed))| *accessed)
+                .map(|(offset, _)| *offset);
+            let offset = match lru {
+                Some(offset) => offset,
+                None => break,
+            };
+            let (block, _) = &self.resident[&offset];
+            let path = self.block_path(offset);
+            write_block(&path, block)?;
+            self.resident.remove(&offset);
+            self.spilled.push(offset);
+        }
+        Ok(())
+    }
+
+    /// Read a spilled block back, removing its file, a block which fails to be read stays spilled
+    fn unspill(&mut self, offset: usize) -> io::Result<Option<Vec<T>>> {
+        let pos = match self.spilled.iter().position(|o| *o == offset) {
+            Some(pos) => pos,
+            None => return Ok(None),
+        };
+        let path = self.block_path(offset);
+        let file = File::open(&path)?;
+        let block = bincode::deserialize_from(BufReader::new(file)).map_err(into_io_error)?;
+        self.spilled.swap_remove(pos);
+        let _ = fs::remove_file(&path);
+        Ok(Some(block))
+    }
+
+    /// Keep the error of a failed write or read for `take_error`
+    fn record<R>(&mut self, result: io::Result<R>) -> Option<R> {
+        match result {
+            Ok(value) => Some(value),
+            Err(err) => {
+                self.error = Some(err);
+                None
+            }
+        }
+    }
+}
+
+fn write_block<T: Serialize>(path: &Path, block: &[T]) -> io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    bincode::serialize_into(&mut file, block).map_err(into_io_error)?;
+    file.flush()
+}
+
+fn into_io_error(err: bincode::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+impl<T> DiskStore<T> {
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Number of blocks which are held in memory
+    pub fn resident_len(&self) -> usize {
+        self.resident.len()
+    }
+
+    /// Number of blocks which have been spilled to files
+    pub fn spilled_len(&self) -> usize {
+        self.spilled.len()
+    }
+
+    /// The most recent error writing or reading a block, if there has been one since this was last called
+    pub fn take_error(&mut self) -> Option<io::Error> {
+        self.error.take()
+    }
+
+    fn block_path(&self, offset: usize) -> PathBuf {
+        self.dir.join(format!("{}.block", offset))
+    }
+}
+
+impl<T: Serialize + DeserializeOwned> BlockStore<T> for DiskStore<T> {
+    fn insert_block(&mut self, offset: usize, block: Vec<T>) {
+        self.clock += 1;
+        self.resident.insert(offset, (block, self.clock));
+        let spilled = self.spill_to_limit(offset);
+        self.record(spilled);
+    }
+
+    fn get_block(&mut self, offset: usize) -> Option<&[T]> {
+        self.clock += 1;
+        if let Some((_, accessed)) = self.resident.get_mut(&offset) {
+            *accessed = self.clock;
+        } else {
+            let unspilled = self.unspill(offset);
+            let block = self.record(unspilled)??;
+            self.resident.insert(offset, (block, self.clock));
+            let spilled = self.spill_to_limit(offset);
+            self.record(spilled);
+        }
+        self.resident.get(&offset).map(|(block, _)| &block[..])
+    }
+
+    fn evict_block(&mut self, offset: usize) -> Option<Vec<T>> {
+        match self.resident.remove(&offset) {
+            Some((block, _)) => Some(block),
+            None => {
+                let unspilled = self.unspill(offset);
+                self.record(unspilled)?
+            }
+        }
+    }
+}
+
+/// Remove the files of any spilled blocks and the store's directory
+impl<T> Drop for DiskStore<T> {
+    fn drop(&mut self) {
+        for offset in &self.spilled {
+            let _ = fs::remove_file(self.block_path(*offset));
+        }
+        let _ = fs::remove_dir(&self.dir);
+    }
+}
+
+#[test]
+fn spill_and_reload() {
+    use crate::store::StoredSparseVec;
+
+    let dir = std::env::temp_dir().join(format!("longpage-disk-store-{}", std::process::id()));
+    let store = DiskStore::new(&dir, 2).unwrap();
+    let mut vec = StoredSparseVec::with_store(100, store);
+    vec.insert_vec(0, vec![0u32, 1, 2]);
+    vec.insert_vec(10, vec![10, 11]);
+    vec.insert_vec(20, vec![20]);
+    assert_eq!(vec.store().spilled_len(), 1);
+    let block_path = vec.store().dir().join("0.block");
+    assert!(block_path.exists());
+    assert_eq!(vec.get(1), Some(&1));
+    assert_eq!(vec.store().resident_len(), 2);
+    assert!(!block_path.exists());
+    vec.remove_range(10..21);
+    assert_eq!(vec.store().spilled_len(), 0);
+    assert_eq!(vec.get(1), Some(&1));
+    assert_eq!(vec.loaded_ranges(), vec![0..3]);
+    drop(vec);
+    fs::remove_dir(&dir).unwrap();
+}
+
+#[test]
+fn stores_sharing_a_directory() {
+    let dir = std::env::temp_dir().join(format!("longpage-shared-dir-{}", std::process::id()));
+    let mut first = DiskStore::new(&dir, 0).unwrap();
+    let mut second = DiskStore::new(&dir, 0).unwrap();
+    assert_ne!(first.dir(), second.dir());
+    first.insert_block(0, vec![1u8]);
+    first.insert_block(10, vec![1u8]);
+    second.insert_block(0, vec![2u8]);
+    second.insert_block(10, vec![2u8]);
+    assert_eq!((first.spilled_len(), second.spilled_len()), (1, 1));
+    assert_eq!(first.evict_block(0), Some(vec![1]));
+    assert_eq!(second.evict_block(0), Some(vec![2]));
+    drop((first, second));
+    fs::remove_dir(&dir).unwrap();
+}
+
+#[test]
+fn missing_block_file_is_an_error() {
+    let dir = std::env::temp_dir().join(format!("longpage-missing-file-{}", std::process::id()));
+    let mut store = DiskStore::new(&dir, 0).unwrap();
+    store.insert_block(0, vec![1u8, 2]);
+    store.insert_block(10, vec![3]);
+    assert!(store.take_error().is_none());
+    fs::remove_file(store.dir().join("0.block")).unwrap();
+    assert_eq!(store.get_block(0), None);
+    assert_eq!(
+        store.take_error().map(|err| err.kind()),
+        Some(io::ErrorKind::NotFound)
+    );
+    assert_eq!(store.spilled_len(), 1);
+    drop(store);
+    fs::remove_dir(&dir).unwrap();
+}
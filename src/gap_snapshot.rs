@@ -0,0 +1,167 @@
+use std::{marker::PhantomData, ops::Range};
+
+use crate::sparse_vec::SparseVec;
+
+/// A snapshot of the unloaded ranges of a SparseVec which answers gap queries in O(log gaps), such as finding the longest gap in a range
+/// It borrows the SparseVec so that it can't go out of date, and must be rebuilt after the SparseVec is modified, so it only pays off for many queries against the same data
+/// The planner doesn't use it, `Coverage` for SparseVec already works from the blocks rather than the items
+#[derive(Debug, Clone)]
+pub struct GapSnapshot<'v> {
+    len: usize,
+    /// All of the gaps in order
+    gaps: Vec<Range<usize>>,
+    /// `longest[k][i]` is the position of the first longest gap in `gaps[i..(i + 2^k)]`
+    longest: Vec<Vec<usize>>,
+    vec: PhantomData<&'v ()>,
+}
+
+impl<'v> GapSnapshot<'v> {
+    /// Build the snapshot in O(gaps log gaps)
+    pub fn new<T>(vec: &'v SparseVec<T>) -> Self {
+        let gaps: Vec<_> = vec.gaps().collect();
+        let mut longest: Vec<Vec<usize>> = vec![(0..gaps.len()).collect()];
+        let mut span = 1;
+        while span * 2 <= gaps.len() {
+            let prev = longest.last().unwrap();
+            let level = (0..=(gaps.len() - span * 2))
+                .map(|i| first_longest(&gaps, prev[i], prev[i + span]))
+                .collect();
+            longest.push(level);
+            span *= 2;
+        }
+        GapSnapshot {
+            len: vec.len(),
+            gaps,
+            longest,
+            vec: PhantomData,
+        }
+    }
+
+    /// The first unloaded range which starts at or after `idx`, or which `idx` is in, clipped to start at `idx`
+    pub fn first_gap_at_or_after(&self, idx: usize) -> Option<Range<usize>> {
+        let gap = &self.gaps[self.gaps.partition_point(|gap| gap.end <= idx)..].first()?;
+        Some(gap.start.max(idx)..gap.end)
+    }
+
+    /// Whether every item in the range is loaded, ranges extending past the end are never loaded
+    pub fn is_range_loaded(&self, idxs: Range<usize>) -> bool {
+        idxs.end <= self.len
+            && self
+                .first_gap_at_or_after(idxs.start)
+                .is_none_or(|gap| gap.start >= idxs.end)
+    }
+
+    /// The ranges within `idxs` which are not loaded, clamped to the length
+    pub fn gaps_in(&self, idxs: Range<usize>) -> impl Iterator<Item = Range<usize>> + '_ {
+        let (first, last) = self.overlapping(&idxs);
+        self.gaps[first..last]
+            .iter()
+            .map(move |gap| gap.start.max(idxs.start)..gap.end.min(idxs.end))
+    }
+
+    /// The longest unloaded range within `idxs`, clipped to it, the first if several are as long
+    pub fn longest_gap_in(&self, idxs: Range<usize>) -> Option<Range<usize>> {
+        let (first, last) = self.overlapping(&idxs);
+        let clip = |pos: usize| {
+            let gap = &self.gaps[pos];
+            gap.start.max(idxs.start)..gap.end.min(idxs.end)
+        };
+        if first == last {
+            return None;
+        }
+        let mut longest = clip(first);
+        if last - first > 2 {
+            let interior = self.gaps[self.longest_in(first + 1, last - 1)].clone();
+            if interior.len() > longest.len() {
+                longest = interior;
+            }
+        }
+        if last - first > 1 && clip(last - 1).len() > longest.len() {
+            longest = clip(last - 1);
+        }
+        Some(longest)
+    }
+
+    /// The positions of the gaps overlapping the range
+    fn overlapping(&self, idxs: &Range<usize>) -> (usize, usize) {
+        if idxs.is_empty() {
+            return (0, 0);
+        }
+        let first = self.gaps.partition_point(|gap| gap.end <= idxs.start);
+        let last = self.gaps.partition_point(|gap| gap.start < idxs.end);
+        (first, last.max(first))
+    }
+
+    /// The position of the first longest gap in `gaps[first..last]`, which must not be empty
+    fn longest_in(&self, first: usize, last: usize) -> usize {
+        let level = (usize::BITS - 1 - (last - first).leading_zeros()) as usize;
+        let span = 1 << level;
+        first_longest(
+            &self.gaps,
+            self.longest[level][first],
+            self.longest[level][last - span],
+        )
+    }
+}
+
+fn first_longest(gaps: &[Range<usize>], a: usize, b: usize) -> usize {
+    if gaps[b].len() > gaps[a].len() {
+        b
+    } else {
+        a.min(b)
+    }
+}
+
+impl<T> SparseVec<T> {
+    /// Take a snapshot of the gaps for answering many gap queries in O(log gaps) while this SparseVec isn't modified
+    pub fn gap_snapshot(&self) -> GapSnapshot<'_> {
+        GapSnapshot::new(self)
+    }
+}
+
+#[test]
+fn queries_match_walking_the_blocks() {
+    let mut vec: SparseVec<u8> = SparseVec::with_len(100);
+    for (start, len) in [
+        (3, 2),
+        (10, 20),
+        (31, 1),
+        (40, 5),
+        (50, 3),
+        (54, 30),
+        (90, 2),
+    ] {
+        vec.insert_vec(start, vec![0; len]);
+    }
+    let snapshot = vec.gap_snapshot();
+    for idx in 0..=100 {
+        assert_eq!(
+            snapshot.first_gap_at_or_after(idx),
+            vec.first_gap_at_or_after(idx),
+            "first gap at or after {}",
+            idx
+        );
+    }
+    for range in [0..100, 5..95, 10..30, 29..33, 35..60, 0..1, 50..50, 95..101] {
+        assert_eq!(
+            snapshot.is_range_loaded(range.clone()),
+            vec.is_range_loaded(range.clone())
+        );
+        assert_eq!(
+            snapshot.gaps_in(range.clone()).collect::<Vec<_>>(),
+            vec.gaps_in(range.clone()).collect::<Vec<_>>()
+        );
+        let walked = vec
+            .gaps_in(range.clone())
+            .fold(None, |longest: Option<Range<usize>>, gap| match longest {
+                Some(longest) if longest.len() >= gap.len() => Some(longest),
+                _ => Some(gap),
+            });
+        assert_eq!(
+            snapshot.longest_gap_in(range.clone()),
+            walked,
+            "{:?}",
+            range
+        );
+    }
+}
@@ -0,0 +1,230 @@
+use std::ops::{Range, RangeBounds};
+
+use crate::{planner::PrefetchConfig, sparse_vec::clamp_range};
+
+/// A rectangle of cells in a grid
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Rect {
+    pub rows: Range<usize>,
+    pub cols: Range<usize>,
+}
+
+impl Rect {
+    pub fn new(rows: Range<usize>, cols: Range<usize>) -> Self {
+        Rect { rows, cols }
+    }
+
+    /// The number of cells
+    pub fn area(&self) -> usize {
+        self.rows.len() * self.cols.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty() || self.cols.is_empty()
+    }
+
+    pub fn contains(&self, row: usize, col: usize) -> bool {
+        self.rows.contains(&row) && self.cols.contains(&col)
+    }
+
+    pub fn intersects(&self, other: &Rect) -> bool {
+        !self.intersection(other).is_empty()
+    }
+
+    /// The cells in both rectangles
+    pub fn intersection(&self, other: &Rect) -> Rect {
+        Rect {
+            rows: self.rows.start.max(other.rows.start)..self.rows.end.min(other.rows.end),
+            cols: self.cols.start.max(other.cols.start)..self.cols.end.min(other.cols.end),
+        }
+    }
+
+    /// The cells of this rectangle outside of `other`, as up to 4 rectangles: full width bands above and below it, and the parts either side of it
+    pub fn subtract(&self, other: &Rect) -> Vec<Rect> {
+        let overlap = self.intersection(other);
+        if overlap.is_empty() {
+            return if self.is_empty() {
+                vec![]
+            } else {
+                vec![self.clone()]
+            };
+        }
+        let pieces = vec![
+            Rect::new(self.rows.start..overlap.rows.start, self.cols.clone()),
+            Rect::new(overlap.rows.end..self.rows.end, self.cols.clone()),
+            Rect::new(overlap.rows.clone(), self.cols.start..overlap.cols.start),
+            Rect::new(overlap.rows.clone(), overlap.cols.end..self.cols.end),
+        ];
+        pieces
+            .into_iter()
+            .filter(|piece| !piece.is_empty())
+            .collect()
+    }
+}
+
+/// Which cells of a grid are loaded, for planning requests when both rows and columns are virtualized
+/// Loaded cells are held as non-overlapping rectangles, the data itself is kept elsewhere
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GridCoverage {
+    rows: usize,
+    cols: usize,
+    loaded: Vec<Rect>,
+}
+
+impl GridCoverage {
+    pub fn with_size(rows: usize, cols: usize) -> Self {
+        GridCoverage {
+            rows,
+            cols,
+            loaded: vec![],
+        }
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    /// Whether the cell is loaded
+    pub fn contains(&self, row: usize, col: usize) -> bool {
+        self.loaded.iter().any(|rect| rect.contains(row, col))
+    }
+
+    /// The loaded rectangles, which don't overlap
+    pub fn loaded(&self) -> &[Rect] {
+        &self.loaded
+    }
+
+    /// Record that the cells in the rectangle are loaded, cells outside of the grid are ignored
+    pub fn mark_loaded(&mut self, rect: Rect) {
+        let mut new = vec![rect.intersection(&self.bounds())];
+        for loaded in &self.loaded {
+            new = new
+                .iter()
+                .flat_map(|piece| piece.subtract(loaded))
+                .collect();
+        }
+        self.loaded
+            .extend(new.into_iter().filter(|piece| !piece.is_empty()));
+    }
+
+    /// Record that the cells in the rectangle are no longer loaded
+    pub fn remove(&mut self, rect: &Rect) {
+        self.loaded = self
+            .loaded
+            .iter()
+            .flat_map(|loaded| loaded.subtract(rect))
+            .collect();
+    }
+
+    /// The cells in the rectangle which aren't loaded, as non-overlapping rectangles
+    pub fn missing_in(&self, rect: &Rect) -> Vec<Rect> {
+        let mut missing = vec![rect.intersection(&self.bounds())];
+        for loaded in &self.loaded {
+            missing = missing
+                .iter()
+                .flat_map(|piece| piece.subtract(loaded))
+                .collect();
+        }
+        missing.retain(|piece| !piece.is_empty());
+        missing
+    }
+
+    pub fn clear(&mut self) {
+        self.loaded.clear();
+    }
+
+    fn bounds(&self) -> Rect {
+        Rect::new(0..self.rows, 0..self.cols)
+    }
+}
+
+/// The rectangles to request to load the window around the rows and columns in view, with the overscan applied to each axis
+/// Rectangles overlapping the view come first, then the largest first
+pub fn next_requests_for_grid(
+    coverage: &GridCoverage,
+    rows_in_view: impl RangeBounds<usize>,
+    cols_in_view: impl RangeBounds<usize>,
+    config: &PrefetchConfig,
+) -> Vec<Rect> {
+    let in_view = Rect::new(
+        clamp_range(coverage.rows, rows_in_view),
+        clamp_range(coverage.cols, cols_in_view),
+    );
+    if in_view.is_empty() {
+        return vec![];
+    }
+    let window = Rect::new(
+        config.window(&in_view.rows, coverage.rows),
+        config.window(&in_view.cols, coverage.cols),
+    );
+    let mut requests = coverage.missing_in(&window);
+    requests.sort_by_key(|rect| (!rect.intersects(&in_view), std::cmp::Reverse(rect.area())));
+    requests
+}
+
+#[test]
+fn subtract_rects() {
+    let outer = Rect::new(0..10, 0..10);
+    assert_eq!(
+        outer.subtract(&Rect::new(2..4, 3..5)),
+        vec![
+            Rect::new(0..2, 0..10),
+            Rect::new(4..10, 0..10),
+            Rect::new(2..4, 0..3),
+            Rect::new(2..4, 5..10),
+        ]
+    );
+    assert_eq!(outer.subtract(&Rect::new(0..10, 0..10)), vec![]);
+    assert_eq!(
+        outer.subtract(&Rect::new(10..20, 0..10)),
+        vec![outer.clone()]
+    );
+}
+
+#[test]
+fn coverage_of_loaded_rects() {
+    let mut grid = GridCoverage::with_size(100, 20);
+    grid.mark_loaded(Rect::new(0..10, 0..10));
+    grid.mark_loaded(Rect::new(5..15, 5..25));
+    assert!(grid.contains(12, 19));
+    assert!(!grid.contains(12, 2));
+    assert_eq!(
+        grid.loaded().iter().map(Rect::area).sum::<usize>(),
+        100 + 150 - 25
+    );
+    grid.remove(&Rect::new(0..100, 8..9));
+    assert!(!grid.contains(6, 8));
+    let mut missing = grid.missing_in(&Rect::new(0..20, 0..10));
+    missing.sort_by_key(|rect| (rect.rows.start, rect.cols.start));
+    assert_eq!(
+        missing,
+        vec![
+            Rect::new(0..10, 8..9),
+            Rect::new(10..15, 0..5),
+            Rect::new(10..15, 8..9),
+            Rect::new(15..20, 0..10),
+        ]
+    );
+}
+
+#[test]
+fn plan_rects_around_view() {
+    let mut grid = GridCoverage::with_size(1000, 100);
+    assert_eq!(
+        next_requests_for_grid(&grid, 100..120, 10..20, &PrefetchConfig::default()),
+        vec![Rect::new(90..130, 5..25)]
+    );
+    grid.mark_loaded(Rect::new(90..130, 5..25));
+    assert_eq!(
+        next_requests_for_grid(&grid, 100..120, 20..30, &PrefetchConfig::default()),
+        vec![Rect::new(90..130, 25..35)]
+    );
+    assert_eq!(
+        next_requests_for_grid(&grid, 120..140, 10..20, &PrefetchConfig::default()),
+        vec![Rect::new(130..150, 5..25)]
+    );
+}
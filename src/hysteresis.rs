@@ -0,0 +1,98 @@
+use std::ops::Range;
+
+use crate::{
+    planner::{Coverage, PrefetchConfig, Request, RequestStrategy},
+    scroll::ScrollHistory,
+};
+
+/// A strategy which only plans once the view has moved more than a threshold since its last requests, or when data in view is missing
+/// This stops every small scroll from making a new request for a sliver of overscan
+#[derive(Debug, Clone)]
+pub struct Hysteresis<S = PrefetchConfig> {
+    strategy: S,
+    threshold: usize,
+    /// The view when requests were last planned
+    last_view: Option<Range<usize>>,
+}
+
+impl<S: RequestStrategy> Hysteresis<S> {
+    /// Plan with `strategy` once either end of the view has moved by more than `threshold` items
+    pub fn new(strategy: S, threshold: usize) -> Self {
+        Hysteresis {
+            strategy,
+            threshold,
+            last_view: None,
+        }
+    }
+
+    /// Whether planning for `in_view` shouldn't be skipped
+    pub fn should_plan(&self, data: &dyn Coverage, in_view: &Range<usize>) -> bool {
+        let last_view = match &self.last_view {
+            Some(last_view) => last_view,
+            None => return true,
+        };
+        let moved = last_view
+            .start
+            .abs_diff(in_view.start)
+            .max(last_view.end.abs_diff(in_view.end));
+        moved > self.threshold || !data.unloaded_in(in_view.clone()).is_empty()
+    }
+
+    /// Plan from scratch on the next call
+    pub fn reset(&mut self) {
+        self.last_view = None;
+    }
+
+    pub fn into_inner(self) -> S {
+        self.strategy
+    }
+}
+
+impl<S: RequestStrategy> RequestStrategy for Hysteresis<S> {
+    fn plan(
+        &mut self,
+        data: &dyn Coverage,
+        in_view: Range<usize>,
+        history: &ScrollHistory,
+    ) -> Vec<Request> {
+        if !self.should_plan(data, &in_view) {
+            return vec![];
+        }
+        let requests = self.strategy.plan(data, in_view.clone(), history);
+        if !requests.is_empty() {
+            self.last_view = Some(in_view);
+        }
+        requests
+    }
+}
+
+#[test]
+fn skip_small_movements() {
+    use std::time::Duration;
+
+    use crate::SparseVec;
+
+    let mut p = SparseVec::<u8>::with_len(1000);
+    let history = ScrollHistory::new(Duration::from_secs(1));
+    let mut strategy = Hysteresis::new(PrefetchConfig::default(), 5);
+    let first = strategy.plan(&p, 100..120, &history);
+    assert_eq!(first[0].range, 90..130);
+    p.insert_vec(90, vec![0; 40]);
+    assert_eq!(strategy.plan(&p, 104..124, &history), vec![]);
+    assert_eq!(strategy.plan(&p, 106..126, &history)[0].range, 130..136);
+}
+
+#[test]
+fn plan_when_view_is_missing_data() {
+    use std::time::Duration;
+
+    use crate::SparseVec;
+
+    let mut p = SparseVec::<u8>::with_len(1000);
+    let history = ScrollHistory::new(Duration::from_secs(1));
+    let mut strategy = Hysteresis::new(PrefetchConfig::default(), 50);
+    strategy.plan(&p, 100..120, &history);
+    p.insert_vec(90, vec![0; 40]);
+    p.remove_range(110..112);
+    assert_eq!(strategy.plan(&p, 101..121, &history)[0].range, 110..112);
+}
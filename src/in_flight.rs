@@ -0,0 +1,165 @@
+use std::ops::Range;
+
+use crate::planner::{cancellable_requests, Coverage, PrefetchConfig};
+
+/// The ranges which have been requested but not received yet, so that the planner doesn't request them again
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InFlight {
+    ranges: Vec<Range<usize>>,
+}
+
+impl InFlight {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record that the range has been requested
+    pub fn start(&mut self, range: Range<usize>) {
+        if !range.is_empty() {
+            self.ranges.push(range);
+        }
+    }
+
+    /// Stop tracking a request once it has completed or failed, returns whether it was being tracked
+    pub fn finish(&mut self, range: &Range<usize>) -> bool {
+        match self.ranges.iter().position(|in_flight| in_flight == range) {
+            Some(pos) => {
+                self.ranges.remove(pos);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The requested ranges in the order they were started
+    pub fn ranges(&self) -> &[Range<usize>] {
+        &self.ranges
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    /// Whether the item at `idx` has been requested
+    pub fn contains(&self, idx: usize) -> bool {
+        self.ranges.iter().any(|range| range.contains(&idx))
+    }
+
+    /// The coverage of `data` with the ranges in flight treated as loaded, pass this to the planner
+    pub fn over<'a, C: Coverage + ?Sized>(&'a self, data: &'a C) -> WithInFlight<'a, C> {
+        WithInFlight::new(data, &self.ranges)
+    }
+
+    /// The requests which don't overlap the window that would be loaded around the view, abort these and then `finish` them
+    pub fn cancellable(&self, in_view: Range<usize>, config: &PrefetchConfig) -> Vec<Range<usize>> {
+        cancellable_requests(in_view, config, &self.ranges)
+    }
+
+    /// Stop tracking all requests
+    pub fn clear(&mut self) {
+        self.ranges.clear();
+    }
+}
+
+/// Coverage which treats ranges which are in flight as loaded
+#[derive(Debug, Clone, Copy)]
+pub struct WithInFlight<'a, C: ?Sized> {
+    data: &'a C,
+    in_flight: &'a [Range<usize>],
+}
+
+impl<'a, C: Coverage + ?Sized> WithInFlight<'a, C> {
+    pub fn new(data: &'a C, in_flight: &'a [Range<usize>]) -> Self {
+        WithInFlight { data, in_flight }
+    }
+}
+
+impl<C: Coverage + ?Sized> Coverage for WithInFlight<'_, C> {
+    fn known_len(&self) -> Option<usize> {
+        self.data.known_len()
+    }
+
+    fn loaded_end(&self) -> usize {
+        let requested_end = self.in_flight.iter().map(|range| range.end).max();
+        let loaded_end = self.data.loaded_end();
+        match (self.data.known_len(), requested_end) {
+            (None, Some(requested_end)) => loaded_end.max(requested_end),
+            _ => loaded_end,
+        }
+    }
+
+    fn unloaded_in(&self, idxs: Range<usize>) -> Vec<Range<usize>> {
+        let mut gaps = self.data.unloaded_in(idxs);
+        for in_flight in self.in_flight {
+            gaps = gaps
+                .into_iter()
+                .flat_map(|gap| {
+                    let before = gap.start..gap.end.min(in_flight.start);
+                    let after = gap.start.max(in_flight.end)..gap.end;
+                    [before, after]
+                })
+                .filter(|gap| !gap.is_empty())
+                .collect();
+        }
+        gaps
+    }
+}
+
+#[test]
+fn in_flight_treated_as_loaded() {
+    use crate::{planner::next_requests_for_view, SparseVec};
+
+    let p = SparseVec::<u8>::with_len(100);
+    let mut in_flight = InFlight::new();
+    in_flight.start(40..50);
+    in_flight.start(55..58);
+    assert_eq!(
+        next_requests_for_view(&in_flight.over(&p), 40..60, &PrefetchConfig::default()),
+        vec![58..70, 30..40, 50..55]
+    );
+    assert!(in_flight.contains(56));
+    assert!(in_flight.finish(&(55..58)));
+    assert!(!in_flight.finish(&(55..58)));
+    assert_eq!(in_flight.ranges().len(), 1);
+}
+
+#[test]
+fn in_flight_past_end_of_unknown_len() {
+    use crate::{planner::next_request_for_view_with, SparseVec};
+
+    let mut p = SparseVec::<u8>::with_unknown_len();
+    p.insert_vec(0, vec![0; 10]);
+    let in_flight = [10..20, 25..28];
+    assert_eq!(
+        next_request_for_view_with(
+            &WithInFlight::new(&p, &in_flight),
+            0..20,
+            &PrefetchConfig::default()
+        ),
+        Some(20..25)
+    );
+}
+
+#[test]
+fn request_past_end_after_in_flight() {
+    use crate::{planner::next_request_past_end, SparseVec};
+
+    let mut p = SparseVec::<u8>::with_unknown_len();
+    p.insert_vec(0, vec![0; 10]);
+    let mut in_flight = InFlight::new();
+    in_flight.start(10..30);
+    assert_eq!(next_request_past_end(&in_flight.over(&p), 20), Some(30..50));
+}
+
+#[test]
+fn cancel_requests_scrolled_away_from() {
+    let mut in_flight = InFlight::new();
+    in_flight.start(0..10);
+    in_flight.start(100..110);
+    let cancel = in_flight.cancellable(100..120, &PrefetchConfig::default());
+    assert_eq!(cancel, vec![0..10]);
+    for range in &cancel {
+        in_flight.finish(range);
+    }
+    assert!(!in_flight.contains(5));
+}
@@ -0,0 +1,107 @@
+use std::time::Duration;
+
+use crate::planner::PrefetchConfig;
+
+/// A smoothed estimate of how long fetches take, used to widen the prefetch window when fetches are slow and narrow it when they are fast
+#[derive(Debug, Clone, PartialEq)]
+pub struct LatencyTracker {
+    /// The weight given to each new sample, between 0 and 1
+    smoothing: f64,
+    estimate: Option<Duration>,
+}
+
+impl LatencyTracker {
+    /// Track latency with each new sample weighted by `smoothing`, 1 uses only the latest sample
+    // Panics if `smoothing` isn't in 0..=1
+    pub fn new(smoothing: f64) -> Self {
+        assert!(
+            (0. ..=1.).contains(&smoothing),
+            "smoothing must be between 0 and 1"
+        );
+        LatencyTracker {
+            smoothing,
+            estimate: None,
+        }
+    }
+
+    /// Record how long a fetch took
+    pub fn record(&mut self, latency: Duration) {
+        self.estimate = Some(match self.estimate {
+            Some(estimate) => Duration::try_from_secs_f64(
+                estimate.as_secs_f64() * (1. - self.smoothing)
+                    + latency.as_secs_f64() * self.smoothing,
+            )
+            .unwrap_or(Duration::MAX),
+            None => latency,
+        });
+    }
+
+    /// The smoothed latency, None before any has been recorded
+    pub fn estimate(&self) -> Option<Duration> {
+        self.estimate
+    }
+
+    pub fn reset(&mut self) {
+        self.estimate = None;
+    }
+
+    /// `base` with its overscan scaled by how much slower or faster fetches are than `reference`, limited to between `1 / max_scale` and `max_scale` times
+    /// With no recorded latency this is `base`, to cover scrolling at a known speed pass the estimate to `ScrollHistory::prefetch_config` instead
+    pub fn prefetch_config(
+        &self,
+        base: &PrefetchConfig,
+        reference: Duration,
+        max_scale: f64,
+    ) -> PrefetchConfig {
+        let mut config = base.clone();
+        let estimate = match self.estimate {
+            Some(estimate) if !reference.is_zero() => estimate,
+            _ => return config,
+        };
+        let max_scale = max_scale.max(1.);
+        let scale =
+            (estimate.as_secs_f64() / reference.as_secs_f64()).clamp(1. / max_scale, max_scale);
+        config.behind = config.behind.scaled(scale);
+        config.ahead = config.ahead.scaled(scale);
+        config
+    }
+}
+
+#[test]
+fn smooth_latency() {
+    let mut latency = LatencyTracker::new(0.5);
+    assert_eq!(latency.estimate(), None);
+    latency.record(Duration::from_millis(100));
+    assert_eq!(latency.estimate(), Some(Duration::from_millis(100)));
+    latency.record(Duration::from_millis(300));
+    assert_eq!(latency.estimate(), Some(Duration::from_millis(200)));
+    latency.reset();
+    assert_eq!(latency.estimate(), None);
+    latency.record(Duration::MAX);
+    latency.record(Duration::MAX);
+    assert_eq!(latency.estimate(), Some(Duration::MAX));
+}
+
+#[test]
+fn scale_overscan_with_latency() {
+    use crate::planner::Overscan;
+
+    let base = PrefetchConfig::new(Overscan::Items(10), Overscan::Fraction(0.5));
+    let reference = Duration::from_millis(100);
+    let mut latency = LatencyTracker::new(1.);
+    assert_eq!(latency.prefetch_config(&base, reference, 4.), base);
+    latency.record(Duration::from_millis(200));
+    let config = latency.prefetch_config(&base, reference, 4.);
+    assert_eq!(config.behind, Overscan::Items(20));
+    assert_eq!(config.ahead, Overscan::Fraction(1.));
+    latency.record(Duration::from_millis(2000));
+    assert_eq!(
+        latency.prefetch_config(&base, reference, 4.).behind,
+        Overscan::Items(40)
+    );
+    latency.record(Duration::from_millis(20));
+    assert_eq!(
+        latency.prefetch_config(&base, reference, 4.).ahead,
+        Overscan::Fraction(0.125)
+    );
+}
@@ -1,54 +1,82 @@
-use std::{
-    ops::{Range, RangeFrom},
-    usize,
-};
+use std::ops::{Range, RangeBounds};
 
-use sparse_vec::SparseVec;
+pub use arc_sparse_vec::ArcSparseVec;
+#[cfg(feature = "bytes")]
+pub use arc_sparse_vec::BytesSparseVec;
+#[cfg(feature = "tokio")]
+pub use async_pager::{AsyncPager, LoadError};
+pub use bidirectional::BidirectionalSparseVec;
+pub use boundaries::PageBoundaries;
+pub use btree::BTreeSparseVec;
+#[cfg(feature = "disk")]
+pub use disk_store::DiskStore;
+pub use gap_snapshot::GapSnapshot;
+pub use grid::{next_requests_for_grid, GridCoverage, Rect};
+pub use hysteresis::Hysteresis;
+pub use in_flight::{InFlight, WithInFlight};
+pub use latency::LatencyTracker;
+pub use load_state::{ItemState, LoadStateSparseVec};
+pub use lru::{Capacity, LruSparseVec};
+#[cfg(feature = "mmap")]
+pub use mmap_store::MmapStore;
+pub use paged::PagedSparseVec;
+pub use pager::Pager;
+pub use planner::{
+    budgeted_requests_for_view, cancellable_requests, next_request_for_view_with,
+    next_request_past_end, next_requests_for_view, next_requests_for_views, plan_for_movement,
+    refresh_requests_for_view, requests_for_tail, tagged_requests_for_view, Coverage, Direction,
+    GapOrder, Movement, Overscan, Plan, PrefetchConfig, Priority, Request, RequestStrategy,
+    SmallGaps,
+};
+pub use queue::{RequestQueue, ScoreWeights, ScoredRequest};
+pub use readahead::Readahead;
+pub use scroll::ScrollHistory;
+pub use shared::SharedSparseVec;
+pub use source::{PageSource, VecSource};
+pub use sparse_vec::{GapError, InsertError, MemoryUsage, MergePolicy, Run, SparseVec, Stats};
+pub use store::{BlockStore, StoredSparseVec, VecStore};
+pub use timestamped::TimestampedSparseVec;
 
-mod sparse_vec;
+pub mod arc_sparse_vec;
+#[cfg(feature = "tokio")]
+pub mod async_pager;
+pub mod bidirectional;
+pub mod boundaries;
+pub mod btree;
+#[cfg(feature = "disk")]
+pub mod disk_store;
+pub mod gap_snapshot;
+pub mod grid;
+pub mod hysteresis;
+pub mod in_flight;
+pub mod latency;
+pub mod load_state;
+pub mod lru;
+#[cfg(feature = "mmap")]
+pub mod mmap_store;
+pub mod paged;
+pub mod pager;
+pub mod planner;
+pub mod queue;
+pub mod readahead;
+pub mod scroll;
+pub mod shared;
+pub mod simulation;
+pub mod source;
+pub mod sparse_vec;
+pub mod store;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+pub mod timestamped;
 
-/// Call this on a change to the viewed data or when ready to make a request. The response specifies which range of records should be requested next. Expects that any previous requests have completed.
-/// Currently will aim to load 50% of the size of the view in either direction
+/// Call this on a change to the viewed data or when ready to make a request. The response specifies which range of records should be requested next. Expects that any previous requests have completed, plan with an `InFlight` over the data to make concurrent requests.
+/// Aims to load 50% of the size of the view in either direction, use `next_request_for_view_with` to configure this
+/// If the length of the data is unknown, the view and the request may extend past its current end
 pub fn next_request_for_view<T>(
     data: &SparseVec<T>,
-    in_view: Range<usize>,
+    in_view: impl RangeBounds<usize>,
 ) -> Option<Range<usize>> {
-    if in_view.len() == 0 {
-        return None;
-    }
-    let extra_load = in_view.len() / 2;
-    let should_load = in_view.start.checked_sub(extra_load).unwrap_or(0)
-        ..(in_view.end + extra_load).min(data.len());
-
-    let mut longest_empty: Option<Range<usize>> = None;
-    let mut current_empty: Option<RangeFrom<usize>> = None;
-    for (i, item) in data.iter_range(should_load.clone()).enumerate() {
-        if item.is_some() {
-            if let Some(current_empty) = current_empty.take() {
-                let current_empty = current_empty.start..(should_load.start + i);
-                if longest_empty.as_ref().map_or(true, |longest_empty| {
-                    longest_empty.len() < current_empty.len()
-                }) {
-                    longest_empty = Some(current_empty);
-                }
-            }
-        } else if current_empty.is_none() {
-            current_empty = Some((should_load.start + i)..);
-        }
-        println!(
-            "{}: longest: {:?} current: {:?}",
-            i, &longest_empty, &current_empty
-        )
-    }
-    if let Some(current_empty) = current_empty.take() {
-        let current_empty = current_empty.start..(should_load.end);
-        if longest_empty.as_ref().map_or(true, |longest_empty| {
-            longest_empty.len() < current_empty.len()
-        }) {
-            longest_empty = Some(current_empty);
-        }
-    }
-    longest_empty
+    next_request_for_view_with(data, in_view, &PrefetchConfig::default())
 }
 
 #[test]
@@ -81,6 +109,23 @@ fn full_view_request_all() {
     assert_eq!(next_request_for_view(&p, 0..20), Some(0..20));
 }
 
+#[test]
+fn request_open_ended_view() {
+    let p = SparseVec::<u8>::with_len(20);
+    assert_eq!(next_request_for_view(&p, 10..), Some(5..20));
+    assert_eq!(next_request_for_view(&p, ..=9), Some(0..15));
+    assert_eq!(next_request_for_view(&p, 30..), None);
+}
+
+#[test]
+fn request_past_end_of_unknown_len() {
+    let mut p = SparseVec::<u8>::with_unknown_len();
+    assert_eq!(next_request_for_view(&p, 0..10), Some(0..15));
+    p.insert_vec(0, (0..10).collect());
+    assert_eq!(next_request_for_view(&p, 5..15), Some(10..20));
+    assert_eq!(next_request_for_view(&p, 20..30), Some(15..35));
+}
+
 #[test]
 fn request_half_after() {
     let mut p = SparseVec::<u8>::with_len(20);
@@ -94,3 +139,13 @@ fn request_half_before() {
     p.insert_vec(10, (10..20).collect());
     assert_eq!(next_request_for_view(&p, 10..20), Some(5..10));
 }
+
+#[test]
+fn request_in_huge_window_without_walking_items() {
+    let mut p = SparseVec::<u8>::with_len(1 << 50);
+    p.insert_vec(1 << 40, vec![0; 10]);
+    assert_eq!(
+        next_request_for_view(&p, 0..(1 << 41)),
+        Some(((1 << 40) + 10)..((1 << 41) + (1 << 40)))
+    );
+}
@@ -0,0 +1,212 @@
+use std::ops::{Deref, Range};
+
+use crate::{
+    lru::forget_range,
+    sparse_vec::{InsertError, SparseVec},
+};
+
+/// The state of an index in a LoadStateSparseVec
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ItemState<T, E> {
+    /// Not loaded or requested
+    Missing,
+    /// A request is in flight
+    Loading,
+    Loaded(T),
+    /// The request failed
+    Failed(E),
+}
+
+/// A SparseVec which also tracks which unloaded ranges are being loaded and which failed to load
+#[derive(Debug)]
+pub struct LoadStateSparseVec<T, E> {
+    vec: SparseVec<T>,
+    /// Ranges with a request in flight, sorted and not overlapping
+    loading: Vec<(Range<usize>, ())>,
+    /// Ranges for which the request failed and the error, sorted and not overlapping
+    failed: Vec<(Range<usize>, E)>,
+}
+
+impl<T, E> LoadStateSparseVec<T, E> {
+    pub fn with_len(len: usize) -> Self {
+        Self::new(SparseVec::with_len(len))
+    }
+
+    pub fn new(vec: SparseVec<T>) -> Self {
+        LoadStateSparseVec {
+            vec,
+            loading: vec![],
+            failed: vec![],
+        }
+    }
+
+    /// Record that a request has been made for the range, clearing any failure in it
+    pub fn mark_loading(&mut self, idxs: Range<usize>)
+    where
+        E: Clone,
+    {
+        forget_range(&mut self.failed, idxs.clone());
+        mark(&mut self.loading, idxs, ());
+    }
+
+    /// Record that the request for the range failed
+    pub fn mark_failed(&mut self, idxs: Range<usize>, err: E)
+    where
+        E: Clone,
+    {
+        forget_range(&mut self.loading, idxs.clone());
+        mark(&mut self.failed, idxs, err);
+    }
+
+    /// Record that the request for the range is no longer in flight, without it having loaded or failed
+    pub fn mark_missing(&mut self, idxs: Range<usize>)
+    where
+        E: Clone,
+    {
+        forget_range(&mut self.loading, idxs.clone());
+        forget_range(&mut self.failed, idxs);
+    }
+
+    /// Insert data into empty space, it is then no longer loading or failed
+    // Panics if space is occupied or the data extends past the end
+    pub fn insert_vec(&mut self, start: usize, vec: Vec<T>)
+    where
+        E: Clone,
+    {
+        if let Err(err) = self.try_insert_vec(start, vec) {
+            panic!("{}", err);
+        }
+    }
+
+    /// Insert data into empty space, it is then no longer loading or failed, or leave the LoadStateSparseVec unchanged if the space is occupied or the data extends past the end
+    pub fn try_insert_vec(&mut self, start: usize, vec: Vec<T>) -> Result<(), InsertError>
+    where
+        E: Clone,
+    {
+        let range = start..(start.saturating_add(vec.len()));
+        self.vec.try_insert_vec(start, vec)?;
+        self.mark_missing(range);
+        Ok(())
+    }
+
+    /// Evict any loaded data in the range, it is then missing
+    pub fn remove_range(&mut self, idxs: Range<usize>)
+    where
+        E: Clone,
+    {
+        self.vec.remove_range(idxs.clone());
+        self.mark_missing(idxs);
+    }
+
+    /// The state of the item at `idx`
+    pub fn state(&self, idx: usize) -> ItemState<&T, &E> {
+        if let Some(item) = self.vec.get(idx) {
+            ItemState::Loaded(item)
+        } else if let Some((_, err)) = self.failed.iter().find(|(range, _)| range.contains(&idx)) {
+            ItemState::Failed(err)
+        } else if self.loading.iter().any(|(range, _)| range.contains(&idx)) {
+            ItemState::Loading
+        } else {
+            ItemState::Missing
+        }
+    }
+
+    /// Iterate over the states of the items in the range, clamped to the length
+    pub fn iter_states(&self, idxs: Range<usize>) -> impl Iterator<Item = ItemState<&T, &E>> + '_ {
+        let idxs = self.vec.clamp_range(idxs);
+        let mut loading = self.loading.iter().peekable();
+        let mut failed = self.failed.iter().peekable();
+        idxs.clone()
+            .zip(self.vec.iter_range(idxs))
+            .map(move |(idx, item)| {
+                while loading.next_if(|(range, _)| range.end <= idx).is_some() {}
+                while failed.next_if(|(range, _)| range.end <= idx).is_some() {}
+                match item {
+                    Some(item) => ItemState::Loaded(item),
+                    None => match failed.peek() {
+                        Some((range, err)) if range.start <= idx => ItemState::Failed(err),
+                        _ => match loading.peek() {
+                            Some((range, _)) if range.start <= idx => ItemState::Loading,
+                            _ => ItemState::Missing,
+                        },
+                    },
+                }
+            })
+    }
+
+    /// The ranges within `idxs` which are neither loaded, loading nor failed, clamped to the length
+    pub fn missing_in(&self, idxs: Range<usize>) -> Vec<Range<usize>> {
+        let mut missing: Vec<_> = self.vec.gaps_in(idxs).map(|gap| (gap, ())).collect();
+        for (range, _) in &self.loading {
+            forget_range(&mut missing, range.clone());
+        }
+        for (range, _) in &self.failed {
+            forget_range(&mut missing, range.clone());
+        }
+        missing.sort_unstable_by_key(|(range, _)| range.start);
+        missing.into_iter().map(|(range, _)| range).collect()
+    }
+
+    pub fn into_inner(self) -> SparseVec<T> {
+        self.vec
+    }
+}
+
+impl<T, E> Deref for LoadStateSparseVec<T, E> {
+    type Target = SparseVec<T>;
+
+    fn deref(&self) -> &SparseVec<T> {
+        &self.vec
+    }
+}
+
+/// Set the metadata of the range, replacing any it overlaps
+fn mark<M: Clone>(tracked: &mut Vec<(Range<usize>, M)>, idxs: Range<usize>, meta: M) {
+    if idxs.is_empty() {
+        return;
+    }
+    forget_range(tracked, idxs.clone());
+    let pos = tracked.partition_point(|(range, _)| range.start < idxs.start);
+    tracked.insert(pos, (idxs, meta));
+}
+
+#[test]
+fn states_through_a_request() {
+    let mut vec = LoadStateSparseVec::<u8, &str>::with_len(10);
+    vec.mark_loading(2..6);
+    assert_eq!(vec.state(3), ItemState::Loading);
+    vec.insert_vec(2, vec![3, 4]);
+    vec.mark_failed(4..6, "timeout");
+    assert_eq!(
+        vec.iter_states(0..8).collect::<Vec<_>>(),
+        vec![
+            ItemState::Missing,
+            ItemState::Missing,
+            ItemState::Loaded(&3),
+            ItemState::Loaded(&4),
+            ItemState::Failed(&"timeout"),
+            ItemState::Failed(&"timeout"),
+            ItemState::Missing,
+            ItemState::Missing,
+        ]
+    );
+    assert_eq!(vec.missing_in(0..10), vec![0..2, 6..10]);
+    vec.mark_loading(5..7);
+    assert_eq!(vec.state(4), ItemState::Failed(&"timeout"));
+    assert_eq!(vec.state(5), ItemState::Loading);
+    assert_eq!(vec.missing_in(0..10), vec![0..2, 7..10]);
+}
+
+#[test]
+fn iter_states_matches_state() {
+    let mut vec = LoadStateSparseVec::<u8, u8>::with_len(20);
+    vec.mark_loading(0..5);
+    vec.mark_failed(3..8, 1);
+    vec.mark_loading(10..12);
+    vec.insert_vec(11, vec![0, 0]);
+    vec.mark_failed(15..16, 2);
+    assert_eq!(
+        vec.iter_states(0..20).collect::<Vec<_>>(),
+        (0..20).map(|idx| vec.state(idx)).collect::<Vec<_>>()
+    );
+}
@@ -0,0 +1,264 @@
+use std::ops::{Deref, Range};
+
+use crate::sparse_vec::{complement, InsertError, SparseVec};
+
+/// Limit on the loaded data held by an LruSparseVec
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Capacity {
+    /// Maximum number of loaded items
+    Items(usize),
+    /// Maximum heap bytes as estimated by `SparseVec::memory_usage`
+    Bytes(usize),
+}
+
+/// A SparseVec which evicts the least recently used blocks when an insert takes it over capacity
+#[derive(Debug)]
+pub struct LruSparseVec<T> {
+    vec: SparseVec<T>,
+    capacity: Capacity,
+    /// Incremented on each access
+    clock: u64,
+    /// The range of each inserted block and the clock at its last access
+    accessed: Vec<(Range<usize>, u64)>,
+    /// Ranges which are never evicted
+    pinned: Vec<Range<usize>>,
+}
+
+impl<T> LruSparseVec<T> {
+    pub fn with_len(len: usize, capacity: Capacity) -> Self {
+        Self::new(SparseVec::with_len(len), capacity)
+    }
+
+    /// Wrap an existing SparseVec, its loaded blocks are treated as not accessed yet
+    pub fn new(vec: SparseVec<T>, capacity: Capacity) -> Self {
+        let accessed = vec.iter_blocks().map(|(range, _)| (range, 0)).collect();
+        let mut lru = LruSparseVec {
+            vec,
+            capacity,
+            clock: 0,
+            accessed,
+            pinned: vec![],
+        };
+        lru.evict_to_capacity(None);
+        lru
+    }
+
+    pub fn capacity(&self) -> Capacity {
+        self.capacity
+    }
+
+    /// Change the capacity, evicting blocks if it is now exceeded
+    pub fn set_capacity(&mut self, capacity: Capacity) {
+        self.capacity = capacity;
+        self.evict_to_capacity(None);
+    }
+
+    /// Mark the blocks overlapping the range as recently used, call this with the range being viewed
+    pub fn touch(&mut self, idxs: Range<usize>) {
+        self.clock += 1;
+        for (range, accessed) in &mut self.accessed {
+            if range.start < idxs.end && idxs.start < range.end {
+                *accessed = self.clock;
+            }
+        }
+    }
+
+    /// Insert data into empty space, then evict least recently used blocks other than this one until within capacity
+    // Panics if space is occupied or the data extends past the end
+    pub fn insert_vec(&mut self, start: usize, vec: Vec<T>) {
+        if let Err(err) = self.try_insert_vec(start, vec) {
+            panic!("{}", err);
+        }
+    }
+
+    /// Insert data into empty space, then evict least recently used blocks other than this one until within capacity
+    pub fn try_insert_vec(&mut self, start: usize, vec: Vec<T>) -> Result<(), InsertError> {
+        let range = start..(start.saturating_add(vec.len()));
+        self.vec.try_insert_vec(start, vec)?;
+        if !range.is_empty() {
+            self.clock += 1;
+            self.accessed.push((range.clone(), self.clock));
+            self.evict_to_capacity(Some(range));
+        }
+        Ok(())
+    }
+
+    /// Evict any loaded data in the range
+    pub fn remove_range(&mut self, idxs: Range<usize>) {
+        self.vec.remove_range(idxs.clone());
+        self.forget(idxs);
+    }
+
+    /// Never evict loaded data in the range, whether to stay within capacity or by `evict_outside`, use this for headers or summary rows
+    pub fn pin(&mut self, range: Range<usize>) {
+        self.pinned.push(range);
+    }
+
+    /// Allow a pinned range to be evicted again, returns whether it was pinned
+    pub fn unpin(&mut self, range: &Range<usize>) -> bool {
+        match self.pinned.iter().position(|pinned| pinned == range) {
+            Some(pos) => {
+                self.pinned.remove(pos);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The pinned ranges
+    pub fn pinned(&self) -> &[Range<usize>] {
+        &self.pinned
+    }
+
+    /// Evict all loaded data outside the range, other than pinned ranges
+    pub fn evict_outside(&mut self, keep: Range<usize>) {
+        self.evict_outside_ranges(&[keep]);
+    }
+
+    /// Evict all loaded data outside of every one of the ranges, other than pinned ranges
+    pub fn evict_outside_ranges(&mut self, keep: &[Range<usize>]) {
+        let keep: Vec<_> = keep.iter().chain(&self.pinned).cloned().collect();
+        for range in complement(&keep, self.vec.len()) {
+            self.remove_range(range);
+        }
+    }
+
+    /// Evict all loaded data
+    pub fn clear(&mut self) {
+        self.vec.clear();
+        self.accessed.clear();
+    }
+
+    pub fn into_inner(self) -> SparseVec<T> {
+        self.vec
+    }
+
+    fn is_over_capacity(&self) -> bool {
+        let usage = self.vec.memory_usage();
+        match self.capacity {
+            Capacity::Items(items) => usage.items > items,
+            Capacity::Bytes(bytes) => usage.heap_bytes > bytes,
+        }
+    }
+
+    /// Evict least recently used blocks until within capacity, never evicting `keep`
+    fn evict_to_capacity(&mut self, keep: Option<Range<usize>>) {
+        while self.is_over_capacity() {
+            let lru = self
+                .accessed
+                .iter()
+                .filter(|(range, _)| Some(range) != keep.as_ref() && !self.is_pinned(range))
+                .min_by_key(|(_, accessed)| *accessed)
+                .map(|(range, _)| range.clone());
+            match lru {
+                Some(range) => self.remove_range(range),
+                None => break,
+            }
+        }
+    }
+
+    /// Whether any of the range is pinned
+    fn is_pinned(&self, range: &Range<usize>) -> bool {
+        self.pinned
+            .iter()
+            .any(|pinned| pinned.start < range.end && range.start < pinned.end)
+    }
+
+    /// Stop tracking access to the range, keeping any parts of tracked blocks outside it
+    fn forget(&mut self, idxs: Range<usize>) {
+        forget_range(&mut self.accessed, idxs);
+    }
+}
+
+/// Stop tracking the range, keeping any parts of tracked blocks outside it along with their metadata
+pub(crate) fn forget_range<M: Clone>(tracked: &mut Vec<(Range<usize>, M)>, idxs: Range<usize>) {
+    let mut remaining = Vec::with_capacity(tracked.len());
+    for (range, meta) in tracked.drain(..) {
+        if range.end <= idxs.start || idxs.end <= range.start {
+            remaining.push((range, meta));
+            continue;
+        }
+        if range.start < idxs.start {
+            remaining.push((range.start..idxs.start, meta.clone()));
+        }
+        if idxs.end < range.end {
+            remaining.push((idxs.end..range.end, meta));
+        }
+    }
+    *tracked = remaining;
+}
+
+impl<T> Deref for LruSparseVec<T> {
+    type Target = SparseVec<T>;
+
+    fn deref(&self) -> &SparseVec<T> {
+        &self.vec
+    }
+}
+
+#[test]
+fn evict_least_recently_inserted() {
+    let mut vec = LruSparseVec::with_len(100, Capacity::Items(6));
+    vec.insert_vec(0, vec![0, 1, 2]);
+    vec.insert_vec(10, vec![10, 11, 12]);
+    vec.insert_vec(20, vec![20, 21]);
+    assert_eq!(
+        vec.loaded_ranges().collect::<Vec<_>>(),
+        vec![10..13, 20..22]
+    );
+}
+
+#[test]
+fn touch_protects_block() {
+    let mut vec = LruSparseVec::with_len(100, Capacity::Items(6));
+    vec.insert_vec(0, vec![0, 1, 2]);
+    vec.insert_vec(10, vec![10, 11, 12]);
+    vec.touch(1..2);
+    vec.insert_vec(20, vec![20, 21]);
+    assert_eq!(vec.loaded_ranges().collect::<Vec<_>>(), vec![0..3, 20..22]);
+}
+
+#[test]
+fn oversized_insert_is_kept() {
+    let mut vec = LruSparseVec::with_len(100, Capacity::Items(2));
+    vec.insert_vec(0, vec![0]);
+    vec.insert_vec(10, vec![10, 11, 12]);
+    assert_eq!(vec.loaded_ranges().collect::<Vec<_>>(), vec![10..13]);
+}
+
+#[test]
+fn partially_removed_block_is_still_tracked() {
+    let mut vec = LruSparseVec::with_len(100, Capacity::Items(5));
+    vec.insert_vec(0, vec![0, 1, 2, 3]);
+    vec.remove_range(1..3);
+    vec.insert_vec(10, vec![10, 11, 12]);
+    vec.insert_vec(20, vec![20]);
+    assert_eq!(
+        vec.loaded_ranges().collect::<Vec<_>>(),
+        vec![3..4, 10..13, 20..21]
+    );
+}
+
+#[test]
+fn byte_capacity() {
+    let mut vec = LruSparseVec::with_len(100, Capacity::Bytes(usize::MAX));
+    vec.insert_vec(0, vec![0u64; 10]);
+    vec.insert_vec(10, vec![0u64; 10]);
+    vec.set_capacity(Capacity::Bytes(vec.memory_usage().heap_bytes - 1));
+    assert_eq!(vec.loaded_ranges().collect::<Vec<_>>(), vec![10..20]);
+}
+
+#[test]
+fn pinned_blocks_are_kept() {
+    let mut vec = LruSparseVec::with_len(100, Capacity::Items(6));
+    vec.pin(0..2);
+    vec.insert_vec(0, vec![0, 1, 2]);
+    vec.insert_vec(10, vec![10, 11, 12]);
+    vec.insert_vec(20, vec![20, 21]);
+    assert_eq!(vec.loaded_ranges().collect::<Vec<_>>(), vec![0..3, 20..22]);
+    vec.evict_outside(20..30);
+    assert_eq!(vec.loaded_ranges().collect::<Vec<_>>(), vec![0..2, 20..22]);
+    assert!(vec.unpin(&(0..2)));
+    vec.evict_outside(20..30);
+    assert_eq!(vec.loaded_ranges().collect::<Vec<_>>(), vec![20..22]);
+}
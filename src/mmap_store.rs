@@ -0,0 +1,101 @@
+use std::{
+    collections::BTreeMap,
+    fs::{File, OpenOptions},
+    io,
+    path::Path,
+};
+
+use memmap2::MmapMut;
+
+use crate::store::BlockStore;
+
+/// A BlockStore for bytes which writes each block into a sparse file at its offset and reads them through a memory map, so that a large file doesn't need to be held on the heap
+#[derive(Debug)]
+pub struct MmapStore {
+    /// `None` for an empty file, which can't be mapped
+    map: Option<MmapMut>,
+    /// Length of each stored block by offset
+    blocks: BTreeMap<usize, usize>,
+    _file: File,
+}
+
+impl MmapStore {
+    /// Create or truncate the file at `path` and size it to hold `len` bytes, most file systems will only allocate the parts which are written
+    pub fn create(path: impl AsRef<Path>, len: usize) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        file.set_len(len as u64)?;
+        let map = if len == 0 {
+            None
+        } else {
+            // Safety: the file was just truncated and is owned by this store, it is undefined behaviour if it is modified by another process while mapped
+            Some(unsafe { MmapMut::map_mut(&file)? })
+        };
+        Ok(MmapStore {
+            map,
+            blocks: BTreeMap::new(),
+            _file: file,
+        })
+    }
+
+    /// Flush written blocks to the file
+    pub fn flush(&self) -> io::Result<()> {
+        match &self.map {
+            Some(map) => map.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl BlockStore<u8> for MmapStore {
+    // Panics if the block extends past the length of the file
+    fn insert_block(&mut self, offset: usize, block: Vec<u8>) {
+        if block.is_empty() {
+            return;
+        }
+        let map = self.map.as_mut().expect("Inserted block past the end");
+        map[offset..(offset + block.len())].copy_from_slice(&block);
+        self.blocks.insert(offset, block.len());
+    }
+
+    fn get_block(&mut self, offset: usize) -> Option<&[u8]> {
+        let len = *self.blocks.get(&offset)?;
+        Some(&self.map.as_ref()?[offset..(offset + len)])
+    }
+
+    /// The bytes are left in the file but are no longer served
+    fn evict_block(&mut self, offset: usize) -> Option<Vec<u8>> {
+        let len = self.blocks.remove(&offset)?;
+        Some(self.map.as_ref()?[offset..(offset + len)].to_vec())
+    }
+}
+
+#[test]
+fn write_and_read_through_map() {
+    use crate::store::StoredSparseVec;
+
+    let path = std::env::temp_dir().join(format!("longpage-mmap-store-{}", std::process::id()));
+    let store = MmapStore::create(&path, 1 << 20).unwrap();
+    let mut vec = StoredSparseVec::with_store(1 << 20, store);
+    vec.insert_vec(1000, b"hello".to_vec());
+    vec.insert_vec(500_000, b"world".to_vec());
+    assert_eq!(vec.get(1001), Some(&b'e'));
+    assert_eq!(
+        vec.get_block_containing(500_002),
+        Some((500_000..500_005, &b"world"[..]))
+    );
+    vec.remove_range(1002..1003);
+    assert_eq!(
+        vec.loaded_ranges(),
+        vec![1000..1002, 1003..1005, 500_000..500_005]
+    );
+    assert_eq!(vec.get(1003), Some(&b'l'));
+    vec.store().flush().unwrap();
+    assert_eq!(std::fs::metadata(&path).unwrap().len(), 1 << 20);
+    drop(vec);
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,176 @@
+use std::ops::{Deref, Range};
+
+use crate::{
+    next_request_for_view,
+    sparse_vec::{InsertError, SparseVec},
+};
+
+const WORD_BITS: usize = u64::BITS as usize;
+
+/// A SparseVec which is loaded in fixed size pages, for backends which serve pages, presence of each page is tracked in a bitset
+#[derive(Debug, Clone)]
+pub struct PagedSparseVec<T> {
+    vec: SparseVec<T>,
+    page_size: usize,
+    /// One bit per page, set while the page is loaded
+    loaded_pages: Vec<u64>,
+}
+
+impl<T> PagedSparseVec<T> {
+    // Panics if `page_size` is 0
+    pub fn with_len(len: usize, page_size: usize) -> Self {
+        assert!(page_size > 0, "Page size must not be 0");
+        let page_count = len.div_ceil(page_size);
+        PagedSparseVec {
+            vec: SparseVec::with_len(len),
+            page_size,
+            loaded_pages: vec![0; page_count.div_ceil(WORD_BITS)],
+        }
+    }
+
+    pub fn page_size(&self) -> usize {
+        self.page_size
+    }
+
+    /// The number of pages, the last of which may be short
+    pub fn page_count(&self) -> usize {
+        self.vec.len().div_ceil(self.page_size)
+    }
+
+    /// The page which holds `idx`
+    pub fn page_of(&self, idx: usize) -> usize {
+        idx / self.page_size
+    }
+
+    /// The indices held by the page, the last page is clipped to the length
+    pub fn page_range(&self, page: usize) -> Range<usize> {
+        let start = page.saturating_mul(self.page_size).min(self.vec.len());
+        start..start.saturating_add(self.page_size).min(self.vec.len())
+    }
+
+    /// The pages which hold any of the indices in the range
+    pub fn pages_in(&self, idxs: Range<usize>) -> Range<usize> {
+        let idxs = idxs.start.min(self.vec.len())..idxs.end.min(self.vec.len());
+        if idxs.is_empty() {
+            return 0..0;
+        }
+        self.page_of(idxs.start)..self.page_of(idxs.end - 1) + 1
+    }
+
+    pub fn is_page_loaded(&self, page: usize) -> bool {
+        self.loaded_pages
+            .get(page / WORD_BITS)
+            .is_some_and(|word| word & (1 << (page % WORD_BITS)) != 0)
+    }
+
+    /// The pages holding any of the range which are not loaded
+    pub fn missing_pages(&self, idxs: Range<usize>) -> impl Iterator<Item = usize> + '_ {
+        self.pages_in(idxs)
+            .filter(move |page| !self.is_page_loaded(*page))
+    }
+
+    /// Insert a whole page
+    // Panics if the page is loaded, is past the end, or if `items` is not the length of the page
+    pub fn insert_page(&mut self, page: usize, items: Vec<T>) {
+        if let Err(err) = self.try_insert_page(page, items) {
+            panic!("{}", err);
+        }
+    }
+
+    /// Insert a whole page, or leave the PagedSparseVec unchanged if the page is loaded or is past the end
+    // Panics if `items` is not the length of the page
+    pub fn try_insert_page(&mut self, page: usize, items: Vec<T>) -> Result<(), InsertError> {
+        let range = self.page_range(page);
+        if range.is_empty() {
+            return Err(InsertError::OutOfBounds {
+                start: page.saturating_mul(self.page_size),
+                len: items.len(),
+            });
+        }
+        if self.is_page_loaded(page) {
+            return Err(InsertError::Overlap(range));
+        }
+        assert_eq!(
+            items.len(),
+            range.len(),
+            "Page {} must have {} items",
+            page,
+            range.len()
+        );
+        self.vec.insert_vec(range.start, items);
+        self.loaded_pages[page / WORD_BITS] |= 1 << (page % WORD_BITS);
+        Ok(())
+    }
+
+    /// Evict the page if it is loaded
+    pub fn remove_page(&mut self, page: usize) {
+        if self.is_page_loaded(page) {
+            self.vec.remove_range(self.page_range(page));
+            self.loaded_pages[page / WORD_BITS] &= !(1 << (page % WORD_BITS));
+        }
+    }
+
+    /// Evict all loaded pages
+    pub fn clear(&mut self) {
+        self.vec.clear();
+        self.loaded_pages.iter_mut().for_each(|word| *word = 0);
+    }
+
+    /// The pages which should be requested next for the view, as `next_request_for_view` but widened to whole pages
+    pub fn next_pages_for_view(&self, in_view: Range<usize>) -> Option<Range<usize>> {
+        let request = next_request_for_view(&self.vec, in_view)?;
+        Some(self.pages_in(request))
+    }
+
+    pub fn into_inner(self) -> SparseVec<T> {
+        self.vec
+    }
+}
+
+impl<T> Deref for PagedSparseVec<T> {
+    type Target = SparseVec<T>;
+
+    fn deref(&self) -> &SparseVec<T> {
+        &self.vec
+    }
+}
+
+#[test]
+fn insert_and_remove_pages() {
+    let mut vec = PagedSparseVec::with_len(250, 100);
+    assert_eq!(vec.page_count(), 3);
+    assert_eq!(vec.page_range(2), 200..250);
+    vec.insert_page(2, vec![0u8; 50]);
+    vec.insert_page(0, vec![0u8; 100]);
+    assert!(vec.is_page_loaded(2));
+    assert!(!vec.is_page_loaded(1));
+    assert_eq!(vec.missing_pages(50..220).collect::<Vec<_>>(), vec![1]);
+    assert_eq!(
+        vec.loaded_ranges().collect::<Vec<_>>(),
+        vec![0..100, 200..250]
+    );
+    assert_eq!(
+        vec.try_insert_page(0, vec![0; 100]),
+        Err(InsertError::Overlap(0..100))
+    );
+    assert_eq!(
+        vec.try_insert_page(3, vec![]),
+        Err(InsertError::OutOfBounds { start: 300, len: 0 })
+    );
+    vec.remove_page(0);
+    assert_eq!(vec.missing_pages(0..250).collect::<Vec<_>>(), vec![0, 1]);
+}
+
+#[test]
+#[should_panic]
+fn insert_short_page() {
+    let mut vec = PagedSparseVec::with_len(250, 100);
+    vec.insert_page(0, vec![0u8; 50]);
+}
+
+#[test]
+fn request_whole_pages() {
+    let mut vec = PagedSparseVec::with_len(1000, 100);
+    vec.insert_page(1, vec![0u8; 100]);
+    assert_eq!(vec.next_pages_for_view(150..290), Some(2..4));
+}
@@ -0,0 +1,193 @@
+use std::ops::Range;
+
+use crate::{
+    in_flight::InFlight,
+    planner::{next_request_for_view_with, Coverage, PrefetchConfig},
+    sparse_vec::{Iter, SparseVec},
+};
+
+/// The load cycle for a view of paged data: holds the data, the viewport and the requests in flight
+/// Set the viewport as it moves, make the requests returned by `poll_request` and pass the responses to `complete`
+#[derive(Debug, Clone)]
+pub struct Pager<T> {
+    data: SparseVec<T>,
+    viewport: Range<usize>,
+    in_flight: InFlight,
+    config: PrefetchConfig,
+    max_in_flight: usize,
+}
+
+impl<T> Pager<T> {
+    /// Page through `data`, starting with an empty viewport at the start, making one request at a time
+    pub fn new(data: SparseVec<T>, config: PrefetchConfig) -> Self {
+        Pager {
+            data,
+            viewport: 0..0,
+            in_flight: InFlight::new(),
+            config,
+            max_in_flight: 1,
+        }
+    }
+
+    /// Allow up to `max_in_flight` requests at once
+    // Panics if `max_in_flight` is 0
+    pub fn with_max_in_flight(self, max_in_flight: usize) -> Self {
+        assert!(max_in_flight > 0, "at least one request must be allowed");
+        Pager {
+            max_in_flight,
+            ..self
+        }
+    }
+
+    /// Move the viewport, requests in flight which are no longer needed are given by `cancellable`
+    pub fn set_viewport(&mut self, viewport: Range<usize>) {
+        self.viewport = viewport;
+    }
+
+    pub fn viewport(&self) -> &Range<usize> {
+        &self.viewport
+    }
+
+    /// The next range to request, which is then tracked as in flight, None if nothing is needed or the limit of requests in flight has been reached
+    pub fn poll_request(&mut self) -> Option<Range<usize>> {
+        if self.in_flight.ranges().len() >= self.max_in_flight {
+            return None;
+        }
+        let request = next_request_for_view_with(
+            &self.in_flight.over(&self.data),
+            self.viewport.clone(),
+            &self.config,
+        )?;
+        self.in_flight.start(request.clone());
+        Some(request)
+    }
+
+    /// Load the response to a request, if fewer items than requested are returned while the length is unknown, the data is taken to end after them
+    /// Items past the end of the range or a known end are dropped
+    pub fn complete(&mut self, range: Range<usize>, mut items: Vec<T>) {
+        self.in_flight.finish(&range);
+        items.truncate(range.len());
+        if items.len() < range.len() && self.data.known_len().is_none() {
+            self.data
+                .set_len(self.data.len().max(range.start + items.len()));
+        }
+        if let Some(len) = self.data.known_len() {
+            items.truncate(len.saturating_sub(range.start));
+        }
+        if !items.is_empty() {
+            self.data.insert_vec_overwrite(range.start, items);
+        }
+    }
+
+    /// Stop tracking a request which failed or was cancelled, so that it can be requested again
+    pub fn fail(&mut self, range: &Range<usize>) {
+        self.in_flight.finish(range);
+    }
+
+    /// The requests in flight which are no longer needed for the viewport, abort these and then `fail` them
+    pub fn cancellable(&self) -> Vec<Range<usize>> {
+        self.in_flight
+            .cancellable(self.viewport.clone(), &self.config)
+    }
+
+    /// The requests which are in flight
+    pub fn in_flight(&self) -> &[Range<usize>] {
+        self.in_flight.ranges()
+    }
+
+    /// Whether all of the items in the viewport are loaded
+    pub fn is_viewport_loaded(&self) -> bool {
+        self.missing_in_viewport().is_empty()
+    }
+
+    /// The ranges in the viewport which aren't loaded, including any past the end of data of unknown length
+    pub fn missing_in_viewport(&self) -> Vec<Range<usize>> {
+        let end = match self.data.known_len() {
+            Some(len) => self.viewport.end.min(len),
+            None => self.viewport.end,
+        };
+        self.data.unloaded_in(self.viewport.start.min(end)..end)
+    }
+
+    /// The items in the viewport, None for those which aren't loaded yet
+    pub fn iter_viewport(&self) -> Iter<'_, T> {
+        self.data.iter_range(self.viewport.clone())
+    }
+
+    pub fn get(&self, idx: usize) -> Option<&T> {
+        self.data.get(idx)
+    }
+
+    pub fn data(&self) -> &SparseVec<T> {
+        &self.data
+    }
+
+    pub fn config_mut(&mut self) -> &mut PrefetchConfig {
+        &mut self.config
+    }
+
+    pub fn into_inner(self) -> SparseVec<T> {
+        self.data
+    }
+}
+
+#[test]
+fn load_cycle() {
+    let mut pager = Pager::new(SparseVec::with_len(100), PrefetchConfig::default());
+    assert_eq!(pager.poll_request(), None);
+    pager.set_viewport(10..20);
+    assert_eq!(pager.poll_request(), Some(5..25));
+    assert_eq!(pager.poll_request(), None);
+    assert!(!pager.is_viewport_loaded());
+    pager.complete(5..25, (5..25).collect());
+    assert!(pager.is_viewport_loaded());
+    assert_eq!(pager.iter_viewport().next(), Some(Some(&10)));
+    assert_eq!(pager.poll_request(), None);
+    pager.set_viewport(15..25);
+    assert_eq!(pager.poll_request(), Some(25..30));
+    assert_eq!(pager.in_flight().first(), Some(&(25..30)));
+}
+
+#[test]
+fn concurrent_requests_and_cancelling() {
+    let mut pager = Pager::new(
+        SparseVec::<usize>::with_len(1000),
+        PrefetchConfig::items(10),
+    )
+    .with_max_in_flight(2);
+    pager.set_viewport(100..120);
+    assert_eq!(pager.poll_request(), Some(90..130));
+    pager.set_viewport(500..520);
+    assert_eq!(pager.poll_request(), Some(490..530));
+    assert_eq!(pager.poll_request(), None);
+    assert_eq!(pager.cancellable(), vec![90..130]);
+    pager.fail(&(90..130));
+    assert!(!pager.in_flight().contains(&(90..130)));
+}
+
+#[test]
+fn short_response_ends_unknown_len() {
+    let mut pager = Pager::new(SparseVec::with_unknown_len(), PrefetchConfig::items(0));
+    pager.set_viewport(0..10);
+    assert_eq!(pager.poll_request(), Some(0..10));
+    pager.complete(0..10, vec![1, 2, 3]);
+    assert_eq!(pager.data().known_len(), Some(3));
+    assert!(pager.is_viewport_loaded());
+    assert_eq!(pager.get(2), Some(&3));
+}
+
+#[test]
+fn long_response_is_truncated_to_request() {
+    let mut pager =
+        Pager::new(SparseVec::with_len(100), PrefetchConfig::items(0)).with_max_in_flight(2);
+    pager.set_viewport(0..10);
+    assert_eq!(pager.poll_request(), Some(0..10));
+    pager.set_viewport(0..20);
+    assert_eq!(pager.poll_request(), Some(10..20));
+    pager.complete(0..10, (0..15).collect());
+    assert_eq!(pager.get(9), Some(&9));
+    assert_eq!(pager.get(10), None);
+    assert!(pager.in_flight().contains(&(10..20)));
+    pager.complete(10..20, (10..20).collect());
+    assert!(pager.is_viewport_loaded());
+}
@@ -0,0 +1,1433 @@
+use std::{
+    cmp::Reverse,
+    ops::{Range, RangeBounds},
+    time::Duration,
+};
+
+use crate::{
+    in_flight::{InFlight, WithInFlight},
+    scroll::ScrollHistory,
+    sparse_vec::{clamp_range, SparseVec},
+    timestamped::TimestampedSparseVec,
+};
+
+/// How far past the view to load
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Overscan {
+    /// A fraction of the length of the view
+    Fraction(f64),
+    /// An absolute number of items
+    Items(usize),
+}
+
+impl Overscan {
+    /// The number of items to load past a view of `view_len` items
+    pub fn items(&self, view_len: usize) -> usize {
+        match *self {
+            Overscan::Fraction(fraction) => (view_len as f64 * fraction.max(0.)) as usize,
+            Overscan::Items(items) => items,
+        }
+    }
+
+    /// The overscan multiplied by `factor`
+    pub fn scaled(&self, factor: f64) -> Overscan {
+        match *self {
+            Overscan::Fraction(fraction) => Overscan::Fraction(fraction * factor.max(0.)),
+            Overscan::Items(items) => Overscan::Items((items as f64 * factor.max(0.)) as usize),
+        }
+    }
+}
+
+/// What the planner needs to know about which data is loaded
+pub trait Coverage {
+    /// The length of the data, None if it isn't known yet
+    fn known_len(&self) -> Option<usize>;
+
+    /// The index after the highest item which is loaded or otherwise accounted for, the length once it is known
+    fn loaded_end(&self) -> usize;
+
+    /// The ranges within `idxs` which aren't loaded in order, anything past the end of data of unknown length is unloaded
+    fn unloaded_in(&self, idxs: Range<usize>) -> Vec<Range<usize>>;
+}
+
+impl<T> Coverage for SparseVec<T> {
+    fn known_len(&self) -> Option<usize> {
+        SparseVec::known_len(self)
+    }
+
+    fn loaded_end(&self) -> usize {
+        self.len()
+    }
+
+    // works from the gaps rather than the items, so that this is O(blocks in the range)
+    fn unloaded_in(&self, idxs: Range<usize>) -> Vec<Range<usize>> {
+        let loaded_end = idxs.end.min(self.len());
+        let mut gaps: Vec<_> = self.gaps_in(idxs.start..loaded_end).collect();
+        let past_end = idxs.start.max(loaded_end)..idxs.end;
+        if !past_end.is_empty() {
+            match gaps.last_mut() {
+                Some(last) if last.end == past_end.start => last.end = past_end.end,
+                _ => gaps.push(past_end),
+            }
+        }
+        gaps
+    }
+}
+
+/// An algorithm for choosing which ranges to request, implement this to try out other heuristics with the rest of the crate
+pub trait RequestStrategy {
+    /// The requests to make to load the data around `in_view`, highest priority first
+    /// `history` holds the recent views, and `self` can keep any state it needs between plans
+    fn plan(
+        &mut self,
+        data: &dyn Coverage,
+        in_view: Range<usize>,
+        history: &ScrollHistory,
+    ) -> Vec<Request>;
+}
+
+/// Configuration for planning requests, the default loads 50% of the size of the view in either direction
+#[derive(Debug, Clone, PartialEq)]
+pub struct PrefetchConfig {
+    /// Overscan before the start of the view
+    pub behind: Overscan,
+    /// Overscan after the end of the view
+    pub ahead: Overscan,
+    /// The largest request to make, larger gaps are requested a chunk at a time starting nearest the view, a limit of 0 is treated as 1
+    pub max_request: Option<usize>,
+    /// Gaps shorter than this are handled according to `small_gaps` rather than being requested alone
+    pub min_request: Option<usize>,
+    pub small_gaps: SmallGaps,
+    /// Gaps separated by at most this many loaded items are requested together, reloading the items between them to save a round trip
+    pub coalesce_within: usize,
+    /// Ranges which should always be loaded wherever the view is, such as headers or summary rows, these are requested after the window around the view
+    pub pinned: Vec<Range<usize>>,
+    /// Which gaps to request first
+    pub order: GapOrder,
+    /// The direction the view is moving, if known the `ahead` overscan is applied in the direction of travel and gaps left behind are requested last
+    pub direction: Option<Direction>,
+}
+
+/// The direction the view is moving through the data
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// Towards higher indices
+    Forward,
+    /// Towards lower indices
+    Backward,
+}
+
+impl Direction {
+    /// The direction of travel from the previous view to the current one, None if it hasn't moved or has grown or shrunk at both ends
+    pub fn between(previous: &Range<usize>, current: &Range<usize>) -> Option<Direction> {
+        use std::cmp::Ordering::*;
+        match (
+            current.start.cmp(&previous.start),
+            current.end.cmp(&previous.end),
+        ) {
+            (Greater, Greater | Equal) | (Equal, Greater) => Some(Direction::Forward),
+            (Less, Less | Equal) | (Equal, Less) => Some(Direction::Backward),
+            _ => None,
+        }
+    }
+
+    /// Whether the range has been left behind by a view moving in this direction
+    fn is_behind(&self, range: &Range<usize>, in_view: &Range<usize>) -> bool {
+        match self {
+            Direction::Forward => range.end <= in_view.start,
+            Direction::Backward => in_view.end <= range.start,
+        }
+    }
+}
+
+/// The order in which to request the gaps around a view
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GapOrder {
+    /// The longest gap first, so that the fewest requests are needed
+    Longest,
+    /// Gaps in view first, then by distance from the view, breaking ties by length
+    Nearest,
+    /// Outwards from the center of the view, so that a jump into the middle of the data fills from where the user is looking
+    CenterOut,
+}
+
+impl GapOrder {
+    /// Gaps with a lower rank are requested first
+    fn rank(&self, gap: &Range<usize>, in_view: &Range<usize>) -> (bool, usize, Reverse<usize>) {
+        match self {
+            GapOrder::Longest => (false, 0, Reverse(gap.len())),
+            GapOrder::Nearest => (
+                !intersects_view(gap, in_view),
+                distance_from_view(gap, in_view),
+                Reverse(gap.len()),
+            ),
+            GapOrder::CenterOut => (
+                false,
+                distance_from_view(gap, &view_center(in_view)),
+                Reverse(gap.len()),
+            ),
+        }
+    }
+
+    /// Where to start splitting a gap into chunks, and how many items of the first chunk should be before that point
+    fn pivot(&self, in_view: &Range<usize>, max_len: usize) -> (usize, usize) {
+        match self {
+            GapOrder::CenterOut => (view_center(in_view).start, max_len / 2),
+            _ => (in_view.start, 0),
+        }
+    }
+
+    /// The rank of the chunks of a split gap
+    fn chunk_rank(
+        &self,
+        chunk: &Range<usize>,
+        in_view: &Range<usize>,
+    ) -> (bool, usize, Reverse<usize>) {
+        match self {
+            GapOrder::CenterOut => self.rank(chunk, in_view),
+            _ => GapOrder::Nearest.rank(chunk, in_view),
+        }
+    }
+}
+
+/// What to do with gaps shorter than the minimum request size
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SmallGaps {
+    /// Don't request them
+    Skip,
+    /// Request them along with the nearest neighbouring gap, reloading the items between
+    Merge,
+}
+
+impl PrefetchConfig {
+    /// Load different amounts before and after the view
+    pub fn new(behind: Overscan, ahead: Overscan) -> Self {
+        PrefetchConfig {
+            behind,
+            ahead,
+            max_request: None,
+            min_request: None,
+            small_gaps: SmallGaps::Merge,
+            coalesce_within: 0,
+            pinned: vec![],
+            order: GapOrder::Longest,
+            direction: None,
+        }
+    }
+
+    /// Load a fraction of the size of the view in either direction
+    pub fn fraction(fraction: f64) -> Self {
+        Self::new(Overscan::Fraction(fraction), Overscan::Fraction(fraction))
+    }
+
+    /// Load a fixed number of items in either direction
+    pub fn items(items: usize) -> Self {
+        Self::new(Overscan::Items(items), Overscan::Items(items))
+    }
+
+    /// For feeds such as chats which start at the end of the data and scroll towards lower indices
+    /// Loads a whole view before the view and a tenth of one after it, and requests the gaps after the view last
+    pub fn bottom_anchored() -> Self {
+        PrefetchConfig {
+            direction: Some(Direction::Backward),
+            ..Self::new(Overscan::Fraction(0.1), Overscan::Fraction(1.))
+        }
+    }
+
+    /// Size requests to cover `per_request` of scrolling when `items_per_second` are consumed, rather than picking `max_request` by hand
+    /// The rate could be measured with `ScrollHistory::velocity` or from how fast a feed is read
+    pub fn sized_for_rate(self, items_per_second: f64, per_request: Duration) -> Self {
+        let items = (items_per_second.abs() * per_request.as_secs_f64()).ceil() as usize;
+        PrefetchConfig {
+            max_request: Some(items.max(1)),
+            ..self
+        }
+    }
+}
+
+impl PrefetchConfig {
+    /// Gaps with a lower rank are requested first
+    fn rank(
+        &self,
+        gap: &Range<usize>,
+        in_view: &Range<usize>,
+    ) -> (bool, (bool, usize, Reverse<usize>)) {
+        let behind = self
+            .direction
+            .is_some_and(|direction| direction.is_behind(gap, in_view));
+        (behind, self.order.rank(gap, in_view))
+    }
+
+    /// The largest request to make, never 0 so that requests aren't empty
+    fn request_limit(&self) -> Option<usize> {
+        self.max_request.map(|max_request| max_request.max(1))
+    }
+
+    /// The window to load around the view
+    pub(crate) fn window(&self, in_view: &Range<usize>, bound: usize) -> Range<usize> {
+        let (before, after) = match self.direction {
+            Some(Direction::Backward) => (&self.ahead, &self.behind),
+            _ => (&self.behind, &self.ahead),
+        };
+        in_view.start.saturating_sub(before.items(in_view.len()))
+            ..in_view
+                .end
+                .saturating_add(after.items(in_view.len()))
+                .min(bound)
+    }
+}
+
+/// Plans as `tagged_requests_for_view`, biased towards the direction of scrolling in `history` if no direction is configured
+impl RequestStrategy for PrefetchConfig {
+    fn plan(
+        &mut self,
+        data: &dyn Coverage,
+        in_view: Range<usize>,
+        history: &ScrollHistory,
+    ) -> Vec<Request> {
+        match (self.direction, history.direction()) {
+            (None, Some(direction)) => {
+                let config = PrefetchConfig {
+                    direction: Some(direction),
+                    ..self.clone()
+                };
+                tagged_requests_for_view(data, in_view, &config)
+            }
+            _ => tagged_requests_for_view(data, in_view, self),
+        }
+    }
+}
+
+impl Default for PrefetchConfig {
+    fn default() -> Self {
+        Self::fraction(0.5)
+    }
+}
+
+/// Like `next_request_for_view`, with the overscan taken from `config`
+pub fn next_request_for_view_with(
+    data: &(impl Coverage + ?Sized),
+    in_view: impl RangeBounds<usize>,
+    config: &PrefetchConfig,
+) -> Option<Range<usize>> {
+    let Candidates {
+        in_view,
+        gaps,
+        pinned,
+        ..
+    } = candidate_gaps(data, in_view, config)?;
+    let mut request = gaps
+        .into_iter()
+        .min_by_key(|gap| config.rank(gap, &in_view))
+        .or_else(|| pinned.into_iter().next());
+    if let (Some(max_request), Some(gap)) = (config.request_limit(), &request) {
+        request = Some(first_chunk(
+            gap.clone(),
+            &in_view,
+            max_request,
+            config.order,
+        ));
+    }
+    #[cfg(feature = "tracing")]
+    tracing::debug!(view = ?in_view, request = ?request, "planned request");
+    request
+}
+
+/// All of the requests needed to load the window around the view, highest priority first, so that they can be made concurrently
+/// The first is the request `next_request_for_view_with` would make, and the rest follow in the configured order. Gaps longer than the maximum request size are split into chunks, nearest to the view first
+pub fn next_requests_for_view(
+    data: &(impl Coverage + ?Sized),
+    in_view: impl RangeBounds<usize>,
+    config: &PrefetchConfig,
+) -> Vec<Range<usize>> {
+    tagged_requests_for_view(data, in_view, config)
+        .into_iter()
+        .map(|request| request.range)
+        .collect()
+}
+
+/// Why a request is needed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    /// Some of the range is in view
+    Visible,
+    /// The range is pinned to always be loaded, and is outside of the window around the view
+    Pinned,
+    /// The range is only overscan around the view
+    Prefetch,
+    /// The range is loaded, but was loaded long enough ago that it should be requested again
+    Refresh,
+}
+
+/// A range to request along with why it's needed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Request {
+    pub range: Range<usize>,
+    pub priority: Priority,
+}
+
+/// Like `next_requests_for_view`, with each request tagged with whether any of it is in view, so that visible data can be requested on a faster path than prefetched data
+pub fn tagged_requests_for_view(
+    data: &(impl Coverage + ?Sized),
+    in_view: impl RangeBounds<usize>,
+    config: &PrefetchConfig,
+) -> Vec<Request> {
+    let Candidates {
+        in_view,
+        gaps,
+        pinned,
+        ..
+    } = match candidate_gaps(data, in_view, config) {
+        Some(candidates) => candidates,
+        None => return vec![],
+    };
+    let mut requests: Vec<_> = order_and_split(gaps, &in_view, config)
+        .into_iter()
+        .map(|range| Request {
+            priority: if intersects_view(&range, &in_view) {
+                Priority::Visible
+            } else {
+                Priority::Prefetch
+            },
+            range,
+        })
+        .collect();
+    requests.extend(
+        order_and_split(pinned, &in_view, config)
+            .into_iter()
+            .map(|range| Request {
+                range,
+                priority: Priority::Pinned,
+            }),
+    );
+    #[cfg(feature = "tracing")]
+    tracing::debug!(view = ?in_view, requests = ?requests, "planned requests");
+    requests
+}
+
+/// Like `tagged_requests_for_view`, with each request split so that its `cost` is within `budget`, for data whose records vary in size
+/// `cost` estimates the cost of requesting a range, such as its size in bytes, and should not decrease as the range grows. A single record over budget is requested alone
+/// Requests before the view are split from their end, so that the chunks nearest the view come first
+pub fn budgeted_requests_for_view(
+    data: &(impl Coverage + ?Sized),
+    in_view: impl RangeBounds<usize>,
+    config: &PrefetchConfig,
+    budget: u64,
+    cost: impl Fn(Range<usize>) -> u64,
+) -> Vec<Request> {
+    let bound = data.known_len().unwrap_or(usize::MAX);
+    let in_view = clamp_range(bound, in_view);
+    tagged_requests_for_view(data, in_view.clone(), config)
+        .into_iter()
+        .flat_map(|request| {
+            let backward = request.range.end <= in_view.start;
+            let priority = request.priority;
+            split_by_cost(request.range, budget, &cost, backward)
+                .into_iter()
+                .map(move |range| Request { range, priority })
+        })
+        .collect()
+}
+
+/// Split the range into the fewest chunks within `budget`, from its end if `backward`
+fn split_by_cost(
+    range: Range<usize>,
+    budget: u64,
+    cost: &impl Fn(Range<usize>) -> u64,
+    backward: bool,
+) -> Vec<Range<usize>> {
+    let mut chunks = vec![];
+    let mut remaining = range;
+    while !remaining.is_empty() {
+        // the most items which fit in the budget, found by binary search as the cost grows with the length
+        let (mut fits, mut over) = (1, remaining.len() + 1);
+        while fits + 1 < over {
+            let len = fits + (over - fits) / 2;
+            let chunk = if backward {
+                (remaining.end - len)..remaining.end
+            } else {
+                remaining.start..(remaining.start + len)
+            };
+            if cost(chunk) <= budget {
+                fits = len;
+            } else {
+                over = len;
+            }
+        }
+        if backward {
+            chunks.push((remaining.end - fits)..remaining.end);
+            remaining.end -= fits;
+        } else {
+            chunks.push(remaining.start..(remaining.start + fits));
+            remaining.start += fits;
+        }
+    }
+    chunks
+}
+
+/// The requests to load the windows around several views of the same data, such as a list and a minimap, with `views` in priority order
+/// The requests for each view come after those for the views before it, and nothing is requested twice
+pub fn next_requests_for_views(
+    data: &(impl Coverage + ?Sized),
+    views: &[Range<usize>],
+    config: &PrefetchConfig,
+) -> Vec<Request> {
+    let mut requests: Vec<Request> = vec![];
+    for view in views {
+        let planned: Vec<_> = requests
+            .iter()
+            .map(|request| request.range.clone())
+            .collect();
+        let data = WithInFlight::new(data, &planned);
+        requests.extend(tagged_requests_for_view(&data, view.clone(), config));
+    }
+    requests
+}
+
+/// Like `tagged_requests_for_view`, but once there are no gaps to request, the ranges around the view which were loaded longer than `ttl` ago are requested with `Priority::Refresh`
+/// Insert the responses to refresh requests with `insert_vec_overwrite`
+pub fn refresh_requests_for_view<T>(
+    data: &TimestampedSparseVec<T>,
+    in_view: impl RangeBounds<usize>,
+    config: &PrefetchConfig,
+    ttl: Duration,
+) -> Vec<Request> {
+    let candidates = match candidate_gaps(&**data, in_view, config) {
+        Some(candidates) => candidates,
+        None => return vec![],
+    };
+    if !candidates.gaps.is_empty() || !candidates.pinned.is_empty() {
+        return tagged_requests_for_view(&**data, candidates.in_view, config);
+    }
+    stale_requests(data, &candidates, config, ttl)
+}
+
+/// Requests to reload the data in the window which is older than `ttl`, whether or not there are gaps in it
+pub(crate) fn stale_requests_for_view<T>(
+    data: &TimestampedSparseVec<T>,
+    in_view: impl RangeBounds<usize>,
+    config: &PrefetchConfig,
+    ttl: Duration,
+) -> Vec<Request> {
+    match candidate_gaps(&**data, in_view, config) {
+        Some(candidates) => stale_requests(data, &candidates, config, ttl),
+        None => vec![],
+    }
+}
+
+fn stale_requests<T>(
+    data: &TimestampedSparseVec<T>,
+    candidates: &Candidates,
+    config: &PrefetchConfig,
+    ttl: Duration,
+) -> Vec<Request> {
+    let should_load = &candidates.should_load;
+    let stale = data
+        .stale_ranges(ttl)
+        .into_iter()
+        .map(|range| range.start.max(should_load.start)..range.end.min(should_load.end))
+        .filter(|range| !range.is_empty())
+        .collect();
+    order_and_split(stale, &candidates.in_view, config)
+        .into_iter()
+        .map(|range| Request {
+            range,
+            priority: Priority::Refresh,
+        })
+        .collect()
+}
+
+/// How the view got to where it is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Movement {
+    /// Gradually, so the data around it is likely to be loaded or requested already
+    Scroll,
+    /// A jump to a far offset, such as a scrollbar drag, so the data around the previous view is no longer needed
+    Seek,
+}
+
+impl Movement {
+    /// A seek if the views don't overlap and the start moved by more than `threshold` lengths of the current view
+    pub fn between(previous: &Range<usize>, current: &Range<usize>, threshold: f64) -> Movement {
+        let moved = previous.start.abs_diff(current.start) as f64;
+        if !intersects_view(previous, current) && moved > current.len() as f64 * threshold {
+            Movement::Seek
+        } else {
+            Movement::Scroll
+        }
+    }
+}
+
+/// The requests to make after the view has moved, and the in-flight requests which are no longer useful
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Plan {
+    pub requests: Vec<Request>,
+    /// In-flight requests which can be aborted
+    pub cancel: Vec<Range<usize>>,
+}
+
+/// Plan the requests for a view which has moved, without requesting anything already in flight
+/// After a scroll this is `tagged_requests_for_view`. After a seek the gradual overscan is dropped, and exactly the view plus `seek_margin` items either side is requested nearest the view first
+/// Either way, the in-flight requests which don't overlap the window to load are to be cancelled
+pub fn plan_for_movement(
+    data: &(impl Coverage + ?Sized),
+    in_view: impl RangeBounds<usize>,
+    movement: Movement,
+    config: &PrefetchConfig,
+    seek_margin: usize,
+    in_flight: &InFlight,
+) -> Plan {
+    let data = in_flight.over(data);
+    match movement {
+        Movement::Scroll => {
+            let in_view = clamp_range(data.known_len().unwrap_or(usize::MAX), in_view);
+            Plan {
+                cancel: cancellable_requests(in_view.clone(), config, in_flight.ranges()),
+                requests: tagged_requests_for_view(&data, in_view, config),
+            }
+        }
+        Movement::Seek => {
+            let bound = data.known_len().unwrap_or(usize::MAX);
+            let in_view = clamp_range(bound, in_view);
+            let config = PrefetchConfig {
+                behind: Overscan::Items(seek_margin),
+                ahead: Overscan::Items(seek_margin),
+                order: GapOrder::Nearest,
+                direction: None,
+                ..config.clone()
+            };
+            Plan {
+                cancel: cancellable_requests(in_view.clone(), &config, in_flight.ranges()),
+                requests: tagged_requests_for_view(&data, in_view, &config),
+            }
+        }
+    }
+}
+
+/// The in-flight requests which don't overlap the window that would be loaded around the view, so can be aborted
+pub fn cancellable_requests(
+    in_view: Range<usize>,
+    config: &PrefetchConfig,
+    in_flight: &[Range<usize>],
+) -> Vec<Range<usize>> {
+    if in_view.is_empty() {
+        return in_flight.to_vec();
+    }
+    let window = config.window(&in_view, usize::MAX);
+    in_flight
+        .iter()
+        .filter(|range| !intersects_view(range, &window))
+        .cloned()
+        .collect()
+}
+
+/// The requests for a view of `view_len` items at the end of the data, to start a bottom-anchored feed, None while the length is unknown
+pub fn requests_for_tail(
+    data: &(impl Coverage + ?Sized),
+    view_len: usize,
+    config: &PrefetchConfig,
+) -> Option<Vec<Request>> {
+    let len = data.known_len()?;
+    Some(tagged_requests_for_view(
+        data,
+        len.saturating_sub(view_len)..len,
+        config,
+    ))
+}
+
+/// For data of unknown length, the next `count` items past the end of what has been loaded, None once the length is known
+/// Use this to page forward through a source which only reports its end when it is reached
+pub fn next_request_past_end(
+    data: &(impl Coverage + ?Sized),
+    count: usize,
+) -> Option<Range<usize>> {
+    if data.known_len().is_some() || count == 0 {
+        return None;
+    }
+    let start = data.loaded_end();
+    Some(start..start.saturating_add(count))
+}
+
+/// The gaps which should be requested to load the window around a view
+struct Candidates {
+    in_view: Range<usize>,
+    should_load: Range<usize>,
+    gaps: Vec<Range<usize>>,
+    /// Missing pinned data outside of the window
+    pinned: Vec<Range<usize>>,
+}
+
+/// The missing parts of the pinned ranges which are outside of the window, in order
+fn pinned_gaps(
+    data: &(impl Coverage + ?Sized),
+    pinned: &[Range<usize>],
+    should_load: &Range<usize>,
+) -> Vec<Range<usize>> {
+    let bound = data.known_len().unwrap_or(usize::MAX);
+    let mut gaps: Vec<Range<usize>> = pinned
+        .iter()
+        .flat_map(|pinned| data.unloaded_in(clamp_range(bound, pinned.clone())))
+        .flat_map(|gap| {
+            vec![
+                gap.start..gap.end.min(should_load.start),
+                gap.start.max(should_load.end)..gap.end,
+            ]
+        })
+        .filter(|gap| !gap.is_empty())
+        .collect();
+    gaps.sort_unstable_by_key(|gap| gap.start);
+    let mut merged: Vec<Range<usize>> = Vec::with_capacity(gaps.len());
+    for gap in gaps {
+        match merged.last_mut() {
+            Some(last) if gap.start <= last.end => last.end = last.end.max(gap.end),
+            _ => merged.push(gap),
+        }
+    }
+    merged
+}
+
+/// Order the ranges to request and split any which are too long, as configured
+fn order_and_split(
+    mut ranges: Vec<Range<usize>>,
+    in_view: &Range<usize>,
+    config: &PrefetchConfig,
+) -> Vec<Range<usize>> {
+    ranges.sort_by_key(|range| config.rank(range, in_view));
+    match config.request_limit() {
+        Some(max_request) => ranges
+            .into_iter()
+            .flat_map(|range| split_gap(range, in_view, max_request, config.order))
+            .collect(),
+        None => ranges,
+    }
+}
+
+/// The candidates for a request, None if the view is empty
+fn candidate_gaps(
+    data: &(impl Coverage + ?Sized),
+    in_view: impl RangeBounds<usize>,
+    config: &PrefetchConfig,
+) -> Option<Candidates> {
+    let bound = data.known_len().unwrap_or(usize::MAX);
+    let in_view = clamp_range(bound, in_view);
+    if in_view.is_empty() {
+        return None;
+    }
+    let should_load = config.window(&in_view, bound);
+    let mut gaps = data.unloaded_in(should_load.clone());
+    if config.coalesce_within > 0 {
+        coalesce_gaps(&mut gaps, config.coalesce_within);
+    }
+    if let Some(min_request) = config.min_request {
+        match config.small_gaps {
+            SmallGaps::Skip => gaps.retain(|gap| gap.len() >= min_request),
+            SmallGaps::Merge => merge_small_gaps(&mut gaps, min_request),
+        }
+    }
+    #[cfg(feature = "tracing")]
+    tracing::debug!(
+        view = ?in_view,
+        window = ?should_load,
+        candidate_gaps = gaps.len(),
+        "found candidate gaps"
+    );
+    let pinned = pinned_gaps(data, &config.pinned, &should_load);
+    Some(Candidates {
+        in_view,
+        should_load,
+        gaps,
+        pinned,
+    })
+}
+
+/// Join neighbouring gaps with at most `within` loaded items between them
+fn coalesce_gaps(gaps: &mut Vec<Range<usize>>, within: usize) {
+    let mut coalesced: Vec<Range<usize>> = Vec::with_capacity(gaps.len());
+    for gap in gaps.drain(..) {
+        match coalesced.last_mut() {
+            Some(last) if gap.start - last.end <= within => last.end = gap.end,
+            _ => coalesced.push(gap),
+        }
+    }
+    *gaps = coalesced;
+}
+
+/// Join each gap shorter than `min_len` to whichever neighbouring gap has fewer loaded items between them
+fn merge_small_gaps(gaps: &mut Vec<Range<usize>>, min_len: usize) {
+    let mut i = 0;
+    while i < gaps.len() {
+        if gaps[i].len() >= min_len || gaps.len() == 1 {
+            i += 1;
+            continue;
+        }
+        let loaded_before = i.checked_sub(1).map(|prev| gaps[i].start - gaps[prev].end);
+        let loaded_after = gaps.get(i + 1).map(|next| next.start - gaps[i].end);
+        let first = match (loaded_before, loaded_after) {
+            (Some(before), Some(after)) if after < before => i,
+            (Some(_), _) => i - 1,
+            (None, _) => i,
+        };
+        gaps[first].end = gaps[first + 1].end;
+        gaps.remove(first + 1);
+        i = first;
+    }
+}
+
+/// The part of `gap` of at most `max_len` items which should be requested first, nearest the view or its center depending on `order`
+fn first_chunk(
+    gap: Range<usize>,
+    in_view: &Range<usize>,
+    max_len: usize,
+    order: GapOrder,
+) -> Range<usize> {
+    if gap.len() <= max_len {
+        return gap;
+    }
+    let (pivot, lead) = order.pivot(in_view, max_len);
+    if gap.end <= pivot {
+        return (gap.end - max_len)..gap.end;
+    }
+    let start = gap
+        .start
+        .max(pivot.saturating_sub(lead))
+        .min(gap.end - max_len);
+    start..(start + max_len)
+}
+
+/// Split `gap` into chunks of at most `max_len` items, ordered from the first chunk outwards
+fn split_gap(
+    gap: Range<usize>,
+    in_view: &Range<usize>,
+    max_len: usize,
+    order: GapOrder,
+) -> Vec<Range<usize>> {
+    let first = first_chunk(gap.clone(), in_view, max_len, order);
+    let mut chunks = vec![first.clone()];
+    let mut end = first.start;
+    while gap.start < end {
+        let start = end.saturating_sub(max_len).max(gap.start);
+        chunks.push(start..end);
+        end = start;
+    }
+    let mut start = first.end;
+    while start < gap.end {
+        let end = start.saturating_add(max_len).min(gap.end);
+        chunks.push(start..end);
+        start = end;
+    }
+    chunks.sort_by_key(|chunk| order.chunk_rank(chunk, in_view));
+    chunks
+}
+
+/// The middle item of the view as a range
+fn view_center(in_view: &Range<usize>) -> Range<usize> {
+    let center = in_view.start + in_view.len() / 2;
+    center..center + 1
+}
+
+/// Whether any of the range is in view
+fn intersects_view(range: &Range<usize>, in_view: &Range<usize>) -> bool {
+    range.start < in_view.end && in_view.start < range.end
+}
+
+/// The number of items between the range and the view, 0 if they overlap or are adjacent
+pub(crate) fn distance_from_view(range: &Range<usize>, in_view: &Range<usize>) -> usize {
+    if range.end <= in_view.start {
+        in_view.start - range.end
+    } else {
+        range.start.saturating_sub(in_view.end)
+    }
+}
+
+#[test]
+fn default_matches_half_view() {
+    let p = SparseVec::<u8>::with_len(100);
+    assert_eq!(
+        next_request_for_view_with(&p, 10..20, &PrefetchConfig::default()),
+        Some(5..25)
+    );
+}
+
+#[test]
+fn request_with_fraction() {
+    let p = SparseVec::<u8>::with_len(100);
+    assert_eq!(
+        next_request_for_view_with(&p, 20..30, &PrefetchConfig::fraction(2.)),
+        Some(0..50)
+    );
+    assert_eq!(
+        next_request_for_view_with(&p, 20..30, &PrefetchConfig::fraction(0.)),
+        Some(20..30)
+    );
+}
+
+#[test]
+fn request_with_item_count() {
+    let mut p = SparseVec::<u8>::with_len(100);
+    assert_eq!(
+        next_request_for_view_with(&p, 20..30, &PrefetchConfig::items(3)),
+        Some(17..33)
+    );
+    p.insert_vec(20, vec![0; 10]);
+    assert_eq!(
+        next_request_for_view_with(&p, 20..30, &PrefetchConfig::items(3)),
+        Some(17..20)
+    );
+}
+
+#[test]
+fn request_asymmetric_overscan() {
+    let p = SparseVec::<u8>::with_len(100);
+    let config = PrefetchConfig::new(Overscan::Fraction(0.1), Overscan::Fraction(1.));
+    assert_eq!(
+        next_request_for_view_with(&p, 40..60, &config),
+        Some(38..80)
+    );
+    let config = PrefetchConfig::new(Overscan::Items(0), Overscan::Items(5));
+    assert_eq!(
+        next_request_for_view_with(&p, 40..60, &config),
+        Some(40..65)
+    );
+}
+
+#[test]
+fn chunk_large_gap_nearest_view() {
+    let mut p = SparseVec::<u8>::with_len(1000);
+    let config = PrefetchConfig {
+        max_request: Some(50),
+        ..PrefetchConfig::default()
+    };
+    assert_eq!(
+        next_request_for_view_with(&p, 400..600, &config),
+        Some(400..450)
+    );
+    p.insert_vec(400, vec![0; 200]);
+    assert_eq!(
+        next_request_for_view_with(&p, 400..600, &config),
+        Some(350..400)
+    );
+    p.insert_vec(300, vec![0; 100]);
+    assert_eq!(
+        next_request_for_view_with(&p, 400..600, &config),
+        Some(600..650)
+    );
+}
+
+#[test]
+fn skip_small_gaps() {
+    let mut p = SparseVec::<u8>::with_len(100);
+    p.insert_vec(0, vec![0; 40]);
+    p.insert_vec(42, vec![0; 58]);
+    let config = PrefetchConfig {
+        min_request: Some(3),
+        small_gaps: SmallGaps::Skip,
+        ..PrefetchConfig::default()
+    };
+    assert_eq!(next_request_for_view_with(&p, 30..50, &config), None);
+    assert_eq!(
+        next_request_for_view_with(&p, 30..50, &PrefetchConfig::default()),
+        Some(40..42)
+    );
+}
+
+#[test]
+fn merge_small_gap_into_nearest_neighbour() {
+    let mut p = SparseVec::<u8>::with_len(100);
+    p.insert_vec(0, vec![0; 40]);
+    p.insert_vec(41, vec![0; 4]);
+    p.insert_vec(50, vec![0; 50]);
+    let config = PrefetchConfig {
+        min_request: Some(3),
+        ..PrefetchConfig::default()
+    };
+    assert_eq!(
+        next_request_for_view_with(&p, 30..50, &config),
+        Some(40..50)
+    );
+    let mut gaps = vec![0..1, 5..6, 20..30];
+    merge_small_gaps(&mut gaps, 3);
+    assert_eq!(gaps, vec![0..6, 20..30]);
+}
+
+#[test]
+fn request_all_gaps_longest_first() {
+    let mut p = SparseVec::<u8>::with_len(100);
+    p.insert_vec(42, vec![0; 5]);
+    p.insert_vec(50, vec![0; 3]);
+    let requests = next_requests_for_view(&p, 40..60, &PrefetchConfig::default());
+    assert_eq!(requests, vec![53..70, 30..42, 47..50]);
+    assert_eq!(
+        next_request_for_view_with(&p, 40..60, &PrefetchConfig::default()),
+        Some(requests[0].clone())
+    );
+    p.insert_vec(0, vec![0; 42]);
+    p.insert_vec(47, vec![0; 3]);
+    p.insert_vec(53, vec![0; 47]);
+    assert_eq!(
+        next_requests_for_view(&p, 40..60, &PrefetchConfig::default()),
+        vec![]
+    );
+}
+
+#[test]
+fn request_all_chunks_nearest_view_first() {
+    let p = SparseVec::<u8>::with_len(100);
+    let config = PrefetchConfig {
+        max_request: Some(10),
+        ..PrefetchConfig::default()
+    };
+    assert_eq!(
+        next_requests_for_view(&p, 40..60, &config),
+        vec![40..50, 50..60, 30..40, 60..70]
+    );
+}
+
+#[test]
+fn tag_visible_and_prefetch_requests() {
+    let mut p = SparseVec::<u8>::with_len(100);
+    p.insert_vec(30, vec![0; 15]);
+    p.insert_vec(50, vec![0; 12]);
+    assert_eq!(
+        tagged_requests_for_view(&p, 40..60, &PrefetchConfig::default()),
+        vec![
+            Request {
+                range: 62..70,
+                priority: Priority::Prefetch
+            },
+            Request {
+                range: 45..50,
+                priority: Priority::Visible
+            },
+        ]
+    );
+}
+
+#[test]
+fn request_nearest_gap_first() {
+    let mut p = SparseVec::<u8>::with_len(100);
+    p.insert_vec(40, vec![0; 9]);
+    p.insert_vec(50, vec![0; 10]);
+    let config = PrefetchConfig {
+        order: GapOrder::Nearest,
+        ..PrefetchConfig::default()
+    };
+    assert_eq!(
+        next_request_for_view_with(&p, 40..60, &config),
+        Some(49..50)
+    );
+    assert_eq!(
+        next_requests_for_view(&p, 40..60, &config),
+        vec![49..50, 30..40, 60..70]
+    );
+    assert_eq!(
+        next_request_for_view_with(&p, 40..60, &PrefetchConfig::default()),
+        Some(30..40)
+    );
+}
+
+#[test]
+fn direction_between_views() {
+    assert_eq!(
+        Direction::between(&(0..10), &(5..15)),
+        Some(Direction::Forward)
+    );
+    assert_eq!(
+        Direction::between(&(5..15), &(0..10)),
+        Some(Direction::Backward)
+    );
+    assert_eq!(
+        Direction::between(&(0..10), &(0..12)),
+        Some(Direction::Forward)
+    );
+    assert_eq!(Direction::between(&(5..15), &(0..20)), None);
+    assert_eq!(Direction::between(&(5..15), &(5..15)), None);
+}
+
+#[test]
+fn bias_towards_direction_of_travel() {
+    let mut p = SparseVec::<u8>::with_len(100);
+    p.insert_vec(40, vec![0; 20]);
+    let mut config = PrefetchConfig {
+        direction: Direction::between(&(30..50), &(40..60)),
+        ..PrefetchConfig::new(Overscan::Items(20), Overscan::Items(5))
+    };
+    assert_eq!(
+        next_requests_for_view(&p, 40..60, &config),
+        vec![60..65, 20..40]
+    );
+    config.direction = Some(Direction::Backward);
+    assert_eq!(
+        next_requests_for_view(&p, 40..60, &config),
+        vec![35..40, 60..80]
+    );
+}
+
+#[test]
+fn coalesce_gaps_separated_by_few_items() {
+    let mut p = SparseVec::<u8>::with_len(100);
+    p.insert_vec(0, vec![0; 40]);
+    p.insert_vec(45, vec![0; 5]);
+    p.insert_vec(55, vec![0; 6]);
+    p.insert_vec(65, vec![0; 35]);
+    let config = PrefetchConfig {
+        coalesce_within: 5,
+        ..PrefetchConfig::default()
+    };
+    assert_eq!(
+        next_requests_for_view(&p, 40..60, &config),
+        vec![40..55, 61..65]
+    );
+    assert_eq!(
+        next_requests_for_view(&p, 40..60, &PrefetchConfig::default()),
+        vec![40..45, 50..55, 61..65]
+    );
+}
+
+#[test]
+fn request_past_end_until_len_known() {
+    let mut p = SparseVec::<u8>::with_unknown_len();
+    assert_eq!(next_request_past_end(&p, 20), Some(0..20));
+    p.insert_vec(0, vec![0; 20]);
+    assert_eq!(next_request_past_end(&p, 20), Some(20..40));
+    p.insert_vec(20, vec![0; 5]);
+    p.set_len(25);
+    assert_eq!(next_request_past_end(&p, 20), None);
+}
+
+#[test]
+fn config_is_a_strategy() {
+    use std::time::{Duration, Instant};
+
+    let mut p = SparseVec::<u8>::with_len(100);
+    p.insert_vec(40, vec![0; 20]);
+    let mut history = ScrollHistory::new(Duration::from_secs(1));
+    let mut strategy = PrefetchConfig::default();
+    assert_eq!(
+        strategy.plan(&p, 40..60, &history),
+        tagged_requests_for_view(&p, 40..60, &PrefetchConfig::default())
+    );
+    let now = Instant::now();
+    history.record_at(50..70, now);
+    history.record_at(40..60, now + Duration::from_millis(10));
+    let plan = strategy.plan(&p, 40..60, &history);
+    assert_eq!(
+        plan.iter()
+            .map(|request| request.range.clone())
+            .collect::<Vec<_>>(),
+        vec![30..40, 60..70]
+    );
+}
+
+#[test]
+fn custom_strategy() {
+    /// Only request what is in view, a page at a time
+    struct VisiblePages(usize);
+
+    impl RequestStrategy for VisiblePages {
+        fn plan(
+            &mut self,
+            data: &dyn Coverage,
+            in_view: Range<usize>,
+            _history: &ScrollHistory,
+        ) -> Vec<Request> {
+            let page_size = self.0;
+            let pages = (in_view.start / page_size * page_size)
+                ..in_view.end.div_ceil(page_size) * page_size;
+            data.unloaded_in(pages)
+                .into_iter()
+                .map(|gap| Request {
+                    range: (gap.start / page_size * page_size)
+                        ..gap.end.div_ceil(page_size) * page_size,
+                    priority: Priority::Visible,
+                })
+                .collect()
+        }
+    }
+
+    let mut p = SparseVec::<u8>::with_len(100);
+    p.insert_vec(20, vec![0; 10]);
+    let strategy: &mut dyn RequestStrategy = &mut VisiblePages(10);
+    let history = ScrollHistory::new(std::time::Duration::from_secs(1));
+    assert_eq!(
+        strategy
+            .plan(&p, 15..35, &history)
+            .into_iter()
+            .map(|request| request.range)
+            .collect::<Vec<_>>(),
+        vec![10..20, 30..40]
+    );
+}
+
+#[test]
+fn request_center_out() {
+    let p = SparseVec::<u8>::with_len(1000);
+    let config = PrefetchConfig {
+        max_request: Some(10),
+        order: GapOrder::CenterOut,
+        ..PrefetchConfig::items(10)
+    };
+    assert_eq!(
+        next_requests_for_view(&p, 500..530, &config),
+        vec![510..520, 520..530, 500..510, 530..540, 490..500]
+    );
+    assert_eq!(
+        next_request_for_view_with(&p, 500..530, &config),
+        Some(510..520)
+    );
+    let mut p = SparseVec::<u8>::with_len(1000);
+    p.insert_vec(512, vec![0; 4]);
+    let config = PrefetchConfig {
+        order: GapOrder::CenterOut,
+        ..PrefetchConfig::items(10)
+    };
+    assert_eq!(
+        next_requests_for_view(&p, 500..530, &config),
+        vec![516..540, 490..512]
+    );
+}
+
+#[test]
+fn refresh_stale_ranges_once_gaps_are_loaded() {
+    use std::time::Instant;
+
+    let now = Instant::now();
+    let mut p = TimestampedSparseVec::<u8>::with_len(100);
+    let hour_ago = now - Duration::from_secs(3600);
+    p.try_insert_vec_at(0, vec![0; 45], hour_ago).unwrap();
+    p.insert_vec(45, vec![0; 10]);
+    let ttl = Duration::from_secs(60);
+    assert_eq!(
+        refresh_requests_for_view(&p, 40..60, &PrefetchConfig::default(), ttl),
+        vec![Request {
+            range: 55..70,
+            priority: Priority::Visible
+        }]
+    );
+    p.try_insert_vec_at(55, vec![0; 45], hour_ago).unwrap();
+    assert_eq!(
+        refresh_requests_for_view(&p, 40..60, &PrefetchConfig::default(), ttl),
+        vec![
+            Request {
+                range: 30..45,
+                priority: Priority::Refresh
+            },
+            Request {
+                range: 55..70,
+                priority: Priority::Refresh
+            },
+        ]
+    );
+}
+
+#[test]
+fn detect_seek() {
+    assert_eq!(Movement::between(&(0..10), &(5..15), 2.), Movement::Scroll);
+    assert_eq!(Movement::between(&(0..10), &(25..35), 2.), Movement::Seek);
+    assert_eq!(Movement::between(&(0..10), &(15..25), 2.), Movement::Scroll);
+    assert_eq!(Movement::between(&(500..510), &(0..10), 2.), Movement::Seek);
+}
+
+#[test]
+fn seek_plan_loads_view_and_cancels_old_requests() {
+    let mut p = SparseVec::<u8>::with_len(1000);
+    p.insert_vec(0, vec![0; 20]);
+    let mut in_flight = InFlight::new();
+    in_flight.start(20..40);
+    in_flight.start(500..505);
+    let config = PrefetchConfig::fraction(1.);
+    let plan = plan_for_movement(&p, 500..520, Movement::Seek, &config, 5, &in_flight);
+    assert_eq!(
+        plan.requests,
+        vec![
+            Request {
+                range: 505..525,
+                priority: Priority::Visible
+            },
+            Request {
+                range: 495..500,
+                priority: Priority::Prefetch
+            },
+        ]
+    );
+    assert_eq!(plan.cancel, vec![20..40]);
+    let plan = plan_for_movement(&p, 10..30, Movement::Scroll, &config, 5, &in_flight);
+    assert_eq!(
+        plan.requests,
+        vec![Request {
+            range: 40..50,
+            priority: Priority::Prefetch
+        }]
+    );
+    assert_eq!(plan.cancel, vec![500..505]);
+}
+
+#[test]
+fn cancel_requests_outside_window() {
+    let in_flight = vec![0..10, 25..35, 45..50, 70..80];
+    let config = PrefetchConfig::new(Overscan::Items(10), Overscan::Items(20));
+    assert_eq!(
+        cancellable_requests(40..50, &config, &in_flight),
+        vec![0..10, 70..80]
+    );
+    assert_eq!(cancellable_requests(40..40, &config, &in_flight), in_flight);
+}
+
+#[test]
+fn request_for_several_views() {
+    let mut p = SparseVec::<u8>::with_len(1000);
+    p.insert_vec(0, vec![0; 100]);
+    let views = vec![90..110, 100..104, 115..135, 500..510, 0..10];
+    assert_eq!(
+        next_requests_for_views(&p, &views, &PrefetchConfig::default())
+            .into_iter()
+            .map(|request| (request.range, request.priority))
+            .collect::<Vec<_>>(),
+        vec![
+            (100..120, Priority::Visible),
+            (120..145, Priority::Visible),
+            (495..515, Priority::Visible),
+        ]
+    );
+}
+
+#[test]
+fn request_pinned_ranges() {
+    let mut p = SparseVec::<u8>::with_len(1000);
+    let config = PrefetchConfig {
+        pinned: vec![0..10, 990..1000, 995..1005],
+        ..PrefetchConfig::default()
+    };
+    assert_eq!(
+        tagged_requests_for_view(&p, 495..505, &config),
+        vec![
+            Request {
+                range: 490..510,
+                priority: Priority::Visible
+            },
+            Request {
+                range: 0..10,
+                priority: Priority::Pinned
+            },
+            Request {
+                range: 990..1000,
+                priority: Priority::Pinned
+            },
+        ]
+    );
+    p.insert_vec(490, vec![0; 20]);
+    p.insert_vec(990, vec![0; 10]);
+    assert_eq!(
+        next_request_for_view_with(&p, 495..505, &config),
+        Some(0..10)
+    );
+    assert_eq!(next_requests_for_view(&p, 2..12, &config), vec![0..17]);
+}
+
+#[test]
+fn split_requests_by_cost() {
+    let p = SparseVec::<u8>::with_len(1000);
+    // records from 500 are 10 times the size of the others
+    let bytes = |range: Range<usize>| {
+        range
+            .map(|idx| if idx >= 500 { 100 } else { 10 })
+            .sum::<u64>()
+    };
+    let requests = budgeted_requests_for_view(&p, 490..510, &PrefetchConfig::default(), 500, bytes);
+    assert_eq!(
+        requests
+            .into_iter()
+            .map(|request| request.range)
+            .collect::<Vec<_>>(),
+        vec![480..503, 503..508, 508..513, 513..518, 518..520]
+    );
+    assert_eq!(
+        split_by_cost(0..10, 25, &bytes, true),
+        vec![8..10, 6..8, 4..6, 2..4, 0..2]
+    );
+    assert_eq!(
+        split_by_cost(600..602, 50, &bytes, false),
+        vec![600..601, 601..602]
+    );
+}
+
+#[test]
+fn bottom_anchored_feed() {
+    let mut p = SparseVec::<u8>::with_len(1000);
+    let config = PrefetchConfig::bottom_anchored();
+    assert_eq!(
+        requests_for_tail(&p, 20, &config),
+        Some(vec![Request {
+            range: 960..1000,
+            priority: Priority::Visible
+        }])
+    );
+    p.insert_vec(960, vec![0; 40]);
+    assert_eq!(
+        next_requests_for_view(&p, 950..970, &config),
+        vec![930..960]
+    );
+    p.insert_vec(930, vec![0; 30]);
+    p.remove_range(970..972);
+    assert_eq!(
+        next_requests_for_view(&p, 940..960, &config),
+        vec![920..930]
+    );
+    assert_eq!(
+        next_requests_for_view(&p, 950..970, &config),
+        vec![970..972]
+    );
+    assert_eq!(
+        requests_for_tail(&SparseVec::<u8>::with_unknown_len(), 20, &config),
+        None
+    );
+}
+
+#[test]
+fn size_requests_for_rate() {
+    let p = SparseVec::<u8>::with_len(1000);
+    let config = PrefetchConfig::items(50).sized_for_rate(40., Duration::from_millis(500));
+    assert_eq!(config.max_request, Some(20));
+    assert_eq!(
+        next_requests_for_view(&p, 100..120, &config),
+        vec![
+            100..120,
+            80..100,
+            120..140,
+            60..80,
+            140..160,
+            50..60,
+            160..170
+        ]
+    );
+    assert_eq!(
+        PrefetchConfig::default()
+            .sized_for_rate(0., Duration::from_secs(1))
+            .max_request,
+        Some(1)
+    );
+}
+
+#[test]
+fn zero_request_limit_requests_one_item() {
+    let p = SparseVec::<u8>::with_len(100);
+    let config = PrefetchConfig {
+        max_request: Some(0),
+        ..PrefetchConfig::default()
+    };
+    let request = next_request_for_view_with(&p, 10..20, &config).unwrap();
+    assert_eq!(request.len(), 1);
+    assert!((10..20).contains(&request.start));
+}
+
+#[test]
+fn zero_request_limit_terminates() {
+    let p = SparseVec::<u8>::with_len(100);
+    let config = PrefetchConfig {
+        max_request: Some(0),
+        ..PrefetchConfig::items(2)
+    };
+    let requests = next_requests_for_view(&p, 10..12, &config);
+    assert_eq!(requests.len(), 6);
+    assert!(requests.iter().all(|request| request.len() == 1));
+    assert_eq!(tagged_requests_for_view(&p, 10..12, &config).len(), 6);
+}
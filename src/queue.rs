@@ -0,0 +1,265 @@
+use std::{
+    ops::{Range, RangeBounds},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    planner::{
+        distance_from_view, stale_requests_for_view, tagged_requests_for_view, Coverage,
+        PrefetchConfig, Priority, Request,
+    },
+    sparse_vec::clamp_range,
+    timestamped::TimestampedSparseVec,
+};
+
+/// How much each property of a request adds to its score, requests with higher scores are more urgent
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScoreWeights {
+    /// Subtracted for each item between the request and the view
+    pub distance: f64,
+    /// Added for each item requested
+    pub size: f64,
+    /// Added for each second since the data being refreshed was loaded
+    pub staleness: f64,
+    /// Added to requests for missing data rather than refreshes
+    pub missing: f64,
+    /// Added to requests for data in view, by default this outweighs the other terms so that data in view is requested first
+    pub visible: f64,
+}
+
+impl Default for ScoreWeights {
+    fn default() -> Self {
+        ScoreWeights {
+            distance: 1.,
+            size: 0.1,
+            staleness: 0.1,
+            missing: 100.,
+            visible: 1000.,
+        }
+    }
+}
+
+/// A candidate request along with what went into its score
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScoredRequest {
+    pub request: Request,
+    /// The number of items between the request and the view
+    pub distance: usize,
+    /// How long ago the first item of the data being refreshed was loaded, zero for missing data
+    pub staleness: Duration,
+    pub score: f64,
+}
+
+impl ScoredRequest {
+    pub fn new(
+        request: Request,
+        distance: usize,
+        staleness: Duration,
+        weights: &ScoreWeights,
+    ) -> Self {
+        let missing = if request.priority == Priority::Refresh {
+            0.
+        } else {
+            weights.missing
+        };
+        let visible = if request.priority == Priority::Visible {
+            weights.visible
+        } else {
+            0.
+        };
+        let score = visible + missing - weights.distance * distance as f64
+            + weights.size * request.range.len() as f64
+            + weights.staleness * staleness.as_secs_f64();
+        ScoredRequest {
+            request,
+            distance,
+            staleness,
+            score,
+        }
+    }
+}
+
+/// Candidate requests ordered by score, for a scheduler which interleaves them with other work using its own policy
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RequestQueue {
+    /// Sorted by ascending score, so the most urgent is last
+    requests: Vec<ScoredRequest>,
+}
+
+impl RequestQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The requests planned for the view, scored with `weights`
+    pub fn for_view(
+        data: &(impl Coverage + ?Sized),
+        in_view: impl RangeBounds<usize>,
+        config: &PrefetchConfig,
+        weights: &ScoreWeights,
+    ) -> Self {
+        let in_view = clamp_range(data.known_len().unwrap_or(usize::MAX), in_view);
+        let mut queue = Self::new();
+        for request in tagged_requests_for_view(data, in_view.clone(), config) {
+            let distance = distance_from_view(&request.range, &in_view);
+            queue.push(ScoredRequest::new(
+                request,
+                distance,
+                Duration::ZERO,
+                weights,
+            ));
+        }
+        queue
+    }
+
+    /// The requests planned for the view along with requests to refresh data in the window older than `ttl`, scored with `weights`
+    pub fn with_refresh<T>(
+        data: &TimestampedSparseVec<T>,
+        in_view: impl RangeBounds<usize>,
+        config: &PrefetchConfig,
+        ttl: Duration,
+        weights: &ScoreWeights,
+    ) -> Self {
+        let in_view = clamp_range(data.known_len().unwrap_or(usize::MAX), in_view);
+        let mut queue = Self::for_view(&**data, in_view.clone(), config, weights);
+        let now = Instant::now();
+        for request in stale_requests_for_view(data, in_view.clone(), config, ttl) {
+            let distance = distance_from_view(&request.range, &in_view);
+            let staleness = data
+                .inserted_at(request.range.start)
+                .map_or(Duration::ZERO, |inserted| {
+                    now.saturating_duration_since(inserted)
+                });
+            queue.push(ScoredRequest::new(request, distance, staleness, weights));
+        }
+        queue
+    }
+
+    pub fn push(&mut self, request: ScoredRequest) {
+        let idx = self
+            .requests
+            .partition_point(|queued| queued.score.total_cmp(&request.score).is_lt());
+        self.requests.insert(idx, request);
+    }
+
+    /// Remove the request with the highest score, of those with equal scores the one queued first
+    pub fn pop(&mut self) -> Option<ScoredRequest> {
+        self.requests.pop()
+    }
+
+    /// The request with the highest score
+    pub fn peek(&self) -> Option<&ScoredRequest> {
+        self.requests.last()
+    }
+
+    pub fn len(&self) -> usize {
+        self.requests.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.requests.is_empty()
+    }
+
+    /// The requests, highest score first
+    pub fn iter(&self) -> impl Iterator<Item = &ScoredRequest> {
+        self.requests.iter().rev()
+    }
+
+    /// Drop requests which overlap the range, such as once it has been loaded by other means
+    pub fn remove_overlapping(&mut self, range: &Range<usize>) {
+        self.requests.retain(|queued| {
+            queued.request.range.end <= range.start || range.end <= queued.request.range.start
+        });
+    }
+
+    /// The requests, highest score first
+    pub fn into_sorted_vec(mut self) -> Vec<ScoredRequest> {
+        self.requests.reverse();
+        self.requests
+    }
+}
+
+#[test]
+fn visible_requests_score_highest() {
+    use crate::SparseVec;
+
+    let mut p = SparseVec::<u8>::with_len(1000);
+    p.insert_vec(100, vec![0; 20]);
+    p.remove_range(110..112);
+    let mut queue = RequestQueue::for_view(
+        &p,
+        100..120,
+        &PrefetchConfig::items(20),
+        &ScoreWeights::default(),
+    );
+    assert_eq!(queue.len(), 3);
+    let first = queue.pop().unwrap();
+    assert_eq!(first.request.range, 110..112);
+    assert_eq!(first.request.priority, Priority::Visible);
+    assert_eq!(first.distance, 0);
+    assert_eq!(
+        queue
+            .iter()
+            .map(|scored| scored.request.range.clone())
+            .collect::<Vec<_>>(),
+        vec![80..100, 120..140]
+    );
+    queue.remove_overlapping(&(100..125));
+    assert_eq!(queue.len(), 1);
+}
+
+#[test]
+fn score_stale_data() {
+    let now = Instant::now();
+    let mut p = TimestampedSparseVec::<u8>::with_len(100);
+    p.try_insert_vec_at(0, vec![0; 10], now - Duration::from_secs(100))
+        .unwrap();
+    p.try_insert_vec_at(10, vec![0; 10], now).unwrap();
+    p.try_insert_vec_at(20, vec![0; 10], now - Duration::from_secs(10))
+        .unwrap();
+    let queue = RequestQueue::with_refresh(
+        &p,
+        10..20,
+        &PrefetchConfig::items(20),
+        Duration::from_secs(5),
+        &ScoreWeights::default(),
+    );
+    let requests = queue.into_sorted_vec();
+    assert_eq!(requests[0].request.range, 30..40);
+    assert_eq!(requests[0].request.priority, Priority::Prefetch);
+    assert_eq!(requests[1].request.range, 0..10);
+    assert_eq!(requests[1].request.priority, Priority::Refresh);
+    assert!(requests[1].staleness >= Duration::from_secs(100));
+    assert_eq!(requests[2].request.range, 20..30);
+    assert_eq!(requests.len(), 3);
+}
+
+#[test]
+fn refresh_past_end_of_unknown_len() {
+    use crate::SparseVec;
+
+    let now = Instant::now();
+    let mut p = TimestampedSparseVec::new(SparseVec::<u8>::with_unknown_len(), now);
+    p.try_insert_vec_at(0, vec![0; 10], now).unwrap();
+    let config = PrefetchConfig::default();
+    let weights = ScoreWeights::default();
+    let ranges = |queue: RequestQueue| {
+        queue
+            .into_sorted_vec()
+            .into_iter()
+            .map(|scored| scored.request.range)
+            .collect::<Vec<_>>()
+    };
+    let expected = ranges(RequestQueue::for_view(&*p, 20..30, &config, &weights));
+    assert_eq!(expected.first(), Some(&(15..35)));
+    assert_eq!(
+        ranges(RequestQueue::with_refresh(
+            &p,
+            20..30,
+            &config,
+            Duration::from_secs(60),
+            &weights
+        )),
+        expected
+    );
+}
@@ -0,0 +1,133 @@
+use std::ops::Range;
+
+use crate::{
+    planner::{
+        tagged_requests_for_view, Coverage, Direction, Overscan, PrefetchConfig, Request,
+        RequestStrategy,
+    },
+    scroll::ScrollHistory,
+};
+
+/// A strategy which doubles the overscan ahead of the view each time it is planned for while the view keeps moving in the same direction, up to a limit, like readahead in a page cache
+/// With no base overscan ahead it grows 1, 2, 4, ... items
+/// It goes back to the base overscan when the view stops or changes direction, so long flings are covered without loading far ahead on every small scroll
+#[derive(Debug, Clone)]
+pub struct Readahead {
+    base: PrefetchConfig,
+    max_ahead: usize,
+    /// The view when last planned for
+    last_view: Option<Range<usize>>,
+    /// The direction of the current run of movements and the overscan ahead it has grown to
+    run: Option<(Direction, usize)>,
+}
+
+impl Readahead {
+    /// Start from the overscan of `base` and grow the overscan ahead up to `max_ahead` items
+    pub fn new(base: PrefetchConfig, max_ahead: usize) -> Self {
+        Readahead {
+            base,
+            max_ahead,
+            last_view: None,
+            run: None,
+        }
+    }
+
+    /// Record the view and get the config to plan for it with
+    pub fn config_for(&mut self, in_view: &Range<usize>) -> PrefetchConfig {
+        let direction = self
+            .last_view
+            .as_ref()
+            .and_then(|last_view| Direction::between(last_view, in_view));
+        self.last_view = Some(in_view.clone());
+        let direction = match direction {
+            Some(direction) => direction,
+            None => {
+                self.run = None;
+                return self.base.clone();
+            }
+        };
+        let ahead = match self.run {
+            Some((run_direction, ahead)) if run_direction == direction => {
+                ahead.saturating_mul(2).max(1).min(self.max_ahead)
+            }
+            _ => self.base.ahead.items(in_view.len()).min(self.max_ahead),
+        };
+        self.run = Some((direction, ahead));
+        PrefetchConfig {
+            ahead: Overscan::Items(ahead.max(self.base.ahead.items(in_view.len()))),
+            direction: Some(direction),
+            ..self.base.clone()
+        }
+    }
+
+    /// The overscan ahead of the view in the current run of movements, None if the view isn't moving
+    pub fn ahead(&self) -> Option<usize> {
+        self.run.map(|(_, ahead)| ahead)
+    }
+
+    /// Start again from the base overscan
+    pub fn reset(&mut self) {
+        self.last_view = None;
+        self.run = None;
+    }
+
+    pub fn into_inner(self) -> PrefetchConfig {
+        self.base
+    }
+}
+
+impl RequestStrategy for Readahead {
+    fn plan(
+        &mut self,
+        data: &dyn Coverage,
+        in_view: Range<usize>,
+        _history: &ScrollHistory,
+    ) -> Vec<Request> {
+        let config = self.config_for(&in_view);
+        tagged_requests_for_view(data, in_view, &config)
+    }
+}
+
+#[test]
+fn grow_while_scrolling() {
+    let mut readahead = Readahead::new(PrefetchConfig::items(10), 50);
+    assert_eq!(readahead.config_for(&(0..20)), PrefetchConfig::items(10));
+    assert_eq!(readahead.config_for(&(5..25)).ahead, Overscan::Items(10));
+    assert_eq!(readahead.config_for(&(10..30)).ahead, Overscan::Items(20));
+    assert_eq!(readahead.config_for(&(15..35)).ahead, Overscan::Items(40));
+    let config = readahead.config_for(&(20..40));
+    assert_eq!(config.ahead, Overscan::Items(50));
+    assert_eq!(config.behind, Overscan::Items(10));
+    assert_eq!(config.direction, Some(Direction::Forward));
+    assert_eq!(readahead.ahead(), Some(50));
+    assert_eq!(readahead.config_for(&(20..40)), PrefetchConfig::items(10));
+    assert_eq!(readahead.ahead(), None);
+}
+
+#[test]
+fn reset_on_change_of_direction() {
+    use crate::SparseVec;
+
+    let p = SparseVec::<u8>::with_len(1000);
+    let history = ScrollHistory::new(std::time::Duration::from_secs(1));
+    let mut readahead = Readahead::new(PrefetchConfig::items(10), 100);
+    readahead.plan(&p, 500..520, &history);
+    readahead.plan(&p, 510..530, &history);
+    readahead.plan(&p, 520..540, &history);
+    assert_eq!(readahead.ahead(), Some(20));
+    let requests = readahead.plan(&p, 510..530, &history);
+    assert_eq!(readahead.ahead(), Some(10));
+    assert_eq!(requests[0].range, 500..540);
+}
+
+#[test]
+fn grow_from_no_overscan() {
+    let mut readahead = Readahead::new(PrefetchConfig::items(0), 8);
+    readahead.config_for(&(0..20));
+    assert_eq!(readahead.config_for(&(5..25)).ahead, Overscan::Items(0));
+    assert_eq!(readahead.config_for(&(10..30)).ahead, Overscan::Items(1));
+    assert_eq!(readahead.config_for(&(15..35)).ahead, Overscan::Items(2));
+    assert_eq!(readahead.config_for(&(20..40)).ahead, Overscan::Items(4));
+    assert_eq!(readahead.config_for(&(25..45)).ahead, Overscan::Items(8));
+    assert_eq!(readahead.config_for(&(30..50)).ahead, Overscan::Items(8));
+}
@@ -0,0 +1,129 @@
+use std::{
+    collections::VecDeque,
+    ops::Range,
+    time::{Duration, Instant},
+};
+
+use crate::planner::{Direction, Overscan, PrefetchConfig};
+
+/// Recent positions of the view, used to scale the prefetch window with the speed of scrolling
+#[derive(Debug, Clone)]
+pub struct ScrollHistory {
+    /// How long a position is kept for after a newer one is recorded
+    window: Duration,
+    /// Each recorded view and when it was shown, oldest first
+    samples: VecDeque<(Instant, Range<usize>)>,
+}
+
+impl ScrollHistory {
+    /// Track the speed of scrolling over the last `window`
+    pub fn new(window: Duration) -> Self {
+        ScrollHistory {
+            window,
+            samples: VecDeque::new(),
+        }
+    }
+
+    /// Record the view shown now
+    pub fn record(&mut self, view: Range<usize>) {
+        self.record_at(view, Instant::now())
+    }
+
+    /// Record the view shown at `at`, which should not be before a previously recorded time
+    pub fn record_at(&mut self, view: Range<usize>, at: Instant) {
+        self.samples.push_back((at, view));
+        while let Some((oldest, _)) = self.samples.front() {
+            if at.saturating_duration_since(*oldest) <= self.window {
+                break;
+            }
+            self.samples.pop_front();
+        }
+    }
+
+    /// The most recently recorded view
+    pub fn current_view(&self) -> Option<&Range<usize>> {
+        self.samples.back().map(|(_, view)| view)
+    }
+
+    /// The speed in items per second over the recorded window, positive when scrolling forward, 0 with fewer than 2 samples
+    pub fn velocity(&self) -> f64 {
+        match (self.samples.front(), self.samples.back()) {
+            (Some((first_at, first)), Some((last_at, last))) => {
+                let elapsed = last_at.saturating_duration_since(*first_at).as_secs_f64();
+                if elapsed == 0. {
+                    return 0.;
+                }
+                (last.start as f64 - first.start as f64) / elapsed
+            }
+            _ => 0.,
+        }
+    }
+
+    /// The direction of scrolling over the recorded window
+    pub fn direction(&self) -> Option<Direction> {
+        let velocity = self.velocity();
+        if velocity > 0. {
+            Some(Direction::Forward)
+        } else if velocity < 0. {
+            Some(Direction::Backward)
+        } else {
+            None
+        }
+    }
+
+    /// `base` with its overscan in the direction of travel extended to cover `lookahead` of scrolling at the current speed, up to `max_views` times the length of the view
+    /// When idle this is `base`, while flinging it can extend several views ahead
+    pub fn prefetch_config(
+        &self,
+        base: &PrefetchConfig,
+        lookahead: Duration,
+        max_views: f64,
+    ) -> PrefetchConfig {
+        let mut config = base.clone();
+        let direction = match self.direction() {
+            Some(direction) => direction,
+            None => return config,
+        };
+        let view_len = self.current_view().map_or(0, |view| view.len());
+        let travel = (self.velocity().abs() * lookahead.as_secs_f64())
+            .min(view_len as f64 * max_views.max(0.)) as usize;
+        config.direction = Some(direction);
+        config.ahead = Overscan::Items(config.ahead.items(view_len).max(travel));
+        config
+    }
+}
+
+#[test]
+fn velocity_over_window() {
+    let start = Instant::now();
+    let mut history = ScrollHistory::new(Duration::from_secs(1));
+    assert_eq!(history.velocity(), 0.);
+    history.record_at(0..10, start);
+    assert_eq!(history.velocity(), 0.);
+    history.record_at(50..60, start + Duration::from_millis(500));
+    assert_eq!(history.velocity(), 100.);
+    history.record_at(20..30, start + Duration::from_millis(1250));
+    assert_eq!(history.velocity(), -40.);
+    assert_eq!(history.direction(), Some(Direction::Backward));
+    assert_eq!(history.current_view(), Some(&(20..30)));
+}
+
+#[test]
+fn scale_overscan_with_velocity() {
+    let start = Instant::now();
+    let base = PrefetchConfig::default();
+    let mut history = ScrollHistory::new(Duration::from_secs(1));
+    history.record_at(100..120, start);
+    history.record_at(100..120, start + Duration::from_millis(100));
+    assert_eq!(
+        history.prefetch_config(&base, Duration::from_secs(1), 5.),
+        base
+    );
+    history.record_at(130..150, start + Duration::from_millis(200));
+    let config = history.prefetch_config(&base, Duration::from_millis(500), 5.);
+    assert_eq!(config.direction, Some(Direction::Forward));
+    assert_eq!(config.ahead, Overscan::Items(75));
+    assert_eq!(config.behind, base.behind);
+    let config = history.prefetch_config(&base, Duration::from_secs(1), 5.);
+    assert_eq!(config.ahead, Overscan::Items(100));
+}
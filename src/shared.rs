@@ -0,0 +1,135 @@
+use std::{
+    ops::Range,
+    sync::{Arc, PoisonError, RwLock},
+};
+
+use crate::{arc_sparse_vec::ArcSparseVec, sparse_vec::InsertError};
+
+/// A sparse vec which can be shared between threads, readers take a cheap snapshot so that they don't hold a lock while a writer inserts
+#[derive(Debug)]
+pub struct SharedSparseVec<T> {
+    // Writes to an ArcSparseVec either complete or leave it unchanged, so a poisoned lock is still consistent
+    vec: RwLock<ArcSparseVec<T>>,
+}
+
+impl<T> SharedSparseVec<T> {
+    pub fn with_len(len: usize) -> Self {
+        SharedSparseVec {
+            vec: RwLock::new(ArcSparseVec::with_len(len)),
+        }
+    }
+
+    /// A snapshot of the current data which has the full read API, this is O(blocks) and later inserts are not seen in it
+    pub fn snapshot(&self) -> ArcSparseVec<T> {
+        self.vec
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    pub fn len(&self) -> usize {
+        self.read(|vec| vec.len())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.read(|vec| vec.is_empty())
+    }
+
+    /// Whether the item at `idx` is loaded
+    pub fn contains(&self, idx: usize) -> bool {
+        self.read(|vec| vec.contains(idx))
+    }
+
+    /// Whether every item in the range is loaded, ranges extending past the end are never loaded
+    pub fn is_range_loaded(&self, idxs: Range<usize>) -> bool {
+        self.read(|vec| vec.is_range_loaded(idxs))
+    }
+
+    /// Insert data into empty space
+    // Panics if space is occupied or the data extends past the end
+    pub fn insert_vec(&self, start: usize, vec: Vec<T>) {
+        if let Err(err) = self.try_insert_vec(start, vec) {
+            panic!("{}", err);
+        }
+    }
+
+    /// Insert data into empty space, or leave the SharedSparseVec unchanged if the space is occupied or the data extends past the end
+    pub fn try_insert_vec(&self, start: usize, vec: Vec<T>) -> Result<(), InsertError> {
+        // convert to a shared block before taking the lock, as that may copy the items
+        self.try_insert_shared(start, vec.into())
+    }
+
+    /// Insert a shared block into empty space without copying it
+    // Panics if space is occupied or the data extends past the end
+    pub fn insert_shared(&self, start: usize, block: Arc<[T]>) {
+        if let Err(err) = self.try_insert_shared(start, block) {
+            panic!("{}", err);
+        }
+    }
+
+    /// Insert a shared block into empty space without copying it, or leave the SharedSparseVec unchanged if the space is occupied or the data extends past the end
+    pub fn try_insert_shared(&self, start: usize, block: Arc<[T]>) -> Result<(), InsertError> {
+        self.write(|vec| vec.try_insert_shared(start, block))
+    }
+
+    /// Evict all loaded data, keeping the length, existing snapshots keep their data
+    pub fn clear(&self) {
+        self.write(|vec| vec.clear())
+    }
+
+    fn read<R>(&self, f: impl FnOnce(&ArcSparseVec<T>) -> R) -> R {
+        f(&self.vec.read().unwrap_or_else(PoisonError::into_inner))
+    }
+
+    fn write<R>(&self, f: impl FnOnce(&mut ArcSparseVec<T>) -> R) -> R {
+        f(&mut self.vec.write().unwrap_or_else(PoisonError::into_inner))
+    }
+}
+
+impl<T> From<ArcSparseVec<T>> for SharedSparseVec<T> {
+    fn from(vec: ArcSparseVec<T>) -> Self {
+        SharedSparseVec {
+            vec: RwLock::new(vec),
+        }
+    }
+}
+
+#[test]
+fn snapshot_is_isolated_from_inserts() {
+    let shared = SharedSparseVec::with_len(10);
+    shared.insert_vec(0, vec![1, 2]);
+    let snapshot = shared.snapshot();
+    shared.insert_vec(5, vec![6]);
+    assert_eq!(snapshot.loaded_ranges().collect::<Vec<_>>(), vec![0..2]);
+    assert!(shared.contains(5));
+    assert_eq!(shared.snapshot().get(5), Some(&6));
+}
+
+#[test]
+fn insert_while_reading_on_other_threads() {
+    use std::thread;
+
+    let shared = Arc::new(SharedSparseVec::with_len(1000));
+    let writer = {
+        let shared = shared.clone();
+        thread::spawn(move || {
+            for start in (0..1000).step_by(100) {
+                shared.insert_vec(start, (start..start + 100).collect());
+            }
+        })
+    };
+    let reader = {
+        let shared = shared.clone();
+        thread::spawn(move || {
+            for _ in 0..100 {
+                let snapshot = shared.snapshot();
+                for (idx, item) in snapshot.iter().enumerate() {
+                    assert!(item.is_none_or(|item| *item == idx));
+                }
+            }
+        })
+    };
+    writer.join().unwrap();
+    reader.join().unwrap();
+    assert!(shared.is_range_loaded(0..1000));
+}
@@ -0,0 +1,202 @@
+//! Replays scroll traces against a mock source to compare request strategies before shipping them
+use std::{
+    convert::TryFrom,
+    ops::Range,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    in_flight::InFlight,
+    planner::{Coverage, RequestStrategy},
+    scroll::ScrollHistory,
+    sparse_vec::SparseVec,
+};
+
+/// A simulated source of `len` items, where a request takes `latency` plus `per_item` for each item requested
+#[derive(Debug, Clone, PartialEq)]
+pub struct MockSource {
+    pub len: usize,
+    pub latency: Duration,
+    pub per_item: Duration,
+    /// The most requests which can be in flight at once, further requests are dropped until one completes
+    pub max_in_flight: Option<usize>,
+}
+
+impl MockSource {
+    pub fn new(len: usize, latency: Duration) -> Self {
+        MockSource {
+            len,
+            latency,
+            per_item: Duration::ZERO,
+            max_in_flight: None,
+        }
+    }
+
+    /// How long a request for the range takes
+    pub fn latency_of(&self, range: &Range<usize>) -> Duration {
+        self.latency
+            .saturating_add(mul_duration(self.per_item, range.len()))
+    }
+}
+
+/// The outcome of a simulation
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Report {
+    pub frames: usize,
+    /// The number of requests issued
+    pub requests: usize,
+    /// The total length of the requests issued
+    pub items_fetched: usize,
+    /// Frames which were rendered with items in view missing
+    pub blank_frames: usize,
+    /// The number of missing items summed over every frame
+    pub blank_items: usize,
+}
+
+/// The views of a scroll at a constant `speed` in items per frame from `start`, for `frames` frames, stopping at the end of the data
+pub fn linear_trace(
+    start: usize,
+    view_len: usize,
+    speed: isize,
+    frames: usize,
+    len: usize,
+) -> Vec<Range<usize>> {
+    let last_start = len.saturating_sub(view_len);
+    (0..frames)
+        .map(|frame| {
+            let offset = speed.unsigned_abs() * frame;
+            let view_start = if speed < 0 {
+                start.saturating_sub(offset)
+            } else {
+                start.saturating_add(offset).min(last_start)
+            };
+            view_start..(view_start + view_len).min(len)
+        })
+        .collect()
+}
+
+/// Replay `trace`, a view for each frame, shown every `frame` against `source`, planning with `strategy` on each frame
+/// Each frame requests are planned over the data already loaded or in flight and issued, completed requests are loaded, then the view is rendered
+pub fn simulate(
+    trace: &[Range<usize>],
+    source: &MockSource,
+    frame: Duration,
+    strategy: &mut (impl RequestStrategy + ?Sized),
+) -> Report {
+    let start = Instant::now();
+    let mut data = SparseVec::<()>::with_len(source.len);
+    let mut in_flight = InFlight::new();
+    let mut pending: Vec<(Duration, Range<usize>)> = vec![];
+    let mut history = ScrollHistory::new(frame * 10);
+    let mut report = Report::default();
+    for (idx, view) in trace.iter().enumerate() {
+        let now = mul_duration(frame, idx);
+        history.record_at(view.clone(), start + now);
+        let requests = strategy.plan(&in_flight.over(&data), view.clone(), &history);
+        for request in requests {
+            if source
+                .max_in_flight
+                .is_some_and(|max| in_flight.ranges().len() >= max)
+            {
+                break;
+            }
+            let range = request.range.start.min(source.len)..request.range.end.min(source.len);
+            if range.is_empty() {
+                continue;
+            }
+            report.requests += 1;
+            report.items_fetched += range.len();
+            in_flight.start(range.clone());
+            pending.push((now.saturating_add(source.latency_of(&range)), range));
+        }
+        pending.retain(|(due, range)| {
+            if *due > now {
+                return true;
+            }
+            data.insert_vec_overwrite(range.start, vec![(); range.len()]);
+            in_flight.finish(range);
+            false
+        });
+        let blank: usize = data
+            .unloaded_in(view.clone())
+            .iter()
+            .map(|gap| gap.len())
+            .sum();
+        if blank > 0 {
+            report.blank_frames += 1;
+            report.blank_items += blank;
+        }
+        report.frames += 1;
+    }
+    report
+}
+
+/// `duration * n`, saturating at `Duration::MAX`
+fn mul_duration(duration: Duration, n: usize) -> Duration {
+    match u32::try_from(n) {
+        Ok(n) => duration.checked_mul(n).unwrap_or(Duration::MAX),
+        Err(_) => {
+            Duration::try_from_secs_f64(duration.as_secs_f64() * n as f64).unwrap_or(Duration::MAX)
+        }
+    }
+}
+
+#[test]
+fn latency_of_huge_requests() {
+    let mut source = MockSource::new(usize::MAX, Duration::from_millis(10));
+    source.per_item = Duration::from_nanos(1);
+    assert_eq!(
+        source.latency_of(&(0..1 << 40)),
+        Duration::from_millis(10) + Duration::from_nanos(1 << 40)
+    );
+    source.per_item = Duration::MAX;
+    assert_eq!(source.latency_of(&(0..2)), Duration::MAX);
+}
+
+#[test]
+fn trace_stops_at_end() {
+    assert_eq!(linear_trace(0, 10, 5, 3, 100), vec![0..10, 5..15, 10..20]);
+    assert_eq!(
+        linear_trace(85, 10, 5, 3, 100),
+        vec![85..95, 90..100, 90..100]
+    );
+    assert_eq!(linear_trace(5, 10, -3, 3, 100), vec![5..15, 2..12, 0..10]);
+}
+
+#[test]
+fn compare_prefetch_configs() {
+    use crate::planner::{Overscan, PrefetchConfig};
+
+    let source = MockSource::new(10_000, Duration::from_millis(100));
+    let frame = Duration::from_millis(16);
+    let trace = linear_trace(0, 20, 4, 100, source.len);
+    let without_prefetch = simulate(&trace, &source, frame, &mut PrefetchConfig::items(0));
+    let with_prefetch = simulate(
+        &trace,
+        &source,
+        frame,
+        &mut PrefetchConfig::new(Overscan::Items(0), Overscan::Items(40)),
+    );
+    assert_eq!(without_prefetch.frames, 100);
+    assert_eq!(without_prefetch.items_fetched, 416);
+    assert!(with_prefetch.blank_frames < without_prefetch.blank_frames);
+    assert_eq!(with_prefetch.blank_frames, 7);
+}
+
+#[test]
+fn limit_requests_in_flight() {
+    use crate::planner::PrefetchConfig;
+
+    let mut source = MockSource::new(1000, Duration::from_millis(50));
+    source.max_in_flight = Some(1);
+    let trace = vec![0..10, 100..110, 100..110, 100..110, 100..110];
+    let report = simulate(
+        &trace,
+        &source,
+        Duration::from_millis(20),
+        &mut PrefetchConfig::items(0),
+    );
+    assert_eq!(report.requests, 2);
+    assert_eq!(report.blank_frames, 5);
+    assert_eq!(report.blank_items, 50);
+}
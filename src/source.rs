@@ -0,0 +1,81 @@
+//! The integration point for backends which paged data is fetched from
+use std::{
+    convert::Infallible,
+    future::{ready, Future},
+    ops::Range,
+    sync::Arc,
+};
+
+/// A backend which paged data is fetched from, implement this to adapt a backend to `AsyncPager` or any other async front-end
+// the length is fetched from the backend, emptiness can be checked from it without another round trip
+#[allow(clippy::len_without_is_empty)]
+pub trait PageSource<T> {
+    type Error;
+
+    /// Fetch the items in the range, fewer may be returned if the data ends within it
+    fn fetch(
+        &self,
+        range: Range<usize>,
+    ) -> impl Future<Output = Result<Vec<T>, Self::Error>> + Send;
+
+    /// The number of items, None if it isn't known
+    fn len(&self) -> impl Future<Output = Option<usize>> + Send;
+}
+
+impl<T, S: PageSource<T> + ?Sized> PageSource<T> for Arc<S> {
+    type Error = S::Error;
+
+    fn fetch(
+        &self,
+        range: Range<usize>,
+    ) -> impl Future<Output = Result<Vec<T>, Self::Error>> + Send {
+        (**self).fetch(range)
+    }
+
+    fn len(&self) -> impl Future<Output = Option<usize>> + Send {
+        (**self).len()
+    }
+}
+
+/// A source of items held in memory, for tests and examples
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VecSource<T> {
+    items: Vec<T>,
+}
+
+impl<T> VecSource<T> {
+    pub fn new(items: Vec<T>) -> Self {
+        VecSource { items }
+    }
+
+    pub fn into_inner(self) -> Vec<T> {
+        self.items
+    }
+}
+
+impl<T: Clone + Send> PageSource<T> for VecSource<T> {
+    type Error = Infallible;
+
+    /// The items in the range, fewer past the end
+    fn fetch(
+        &self,
+        range: Range<usize>,
+    ) -> impl Future<Output = Result<Vec<T>, Self::Error>> + Send {
+        let end = range.end.min(self.items.len());
+        ready(Ok(self.items[range.start.min(end)..end].to_vec()))
+    }
+
+    fn len(&self) -> impl Future<Output = Option<usize>> + Send {
+        ready(Some(self.items.len()))
+    }
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn fetch_from_vec() {
+    let source = Arc::new(VecSource::new((0..10).collect::<Vec<u8>>()));
+    assert_eq!(source.len().await, Some(10));
+    assert_eq!(source.fetch(2..5).await, Ok(vec![2, 3, 4]));
+    assert_eq!(source.fetch(8..20).await, Ok(vec![8, 9]));
+    assert_eq!(source.fetch(15..20).await, Ok(vec![]));
+}
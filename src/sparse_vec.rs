@@ -1,290 +1,2634 @@
 use std::{
-    iter::{Skip},
-    ops::Range,
-    slice,
+    error::Error,
+    fmt,
+    iter::{FromIterator, FusedIterator, Peekable, Rev},
+    marker::PhantomData,
+    mem,
+    ops::{Bound, Index, Range, RangeBounds},
+    slice, vec,
 };
 
-#[derive(Debug)]
+mod cursor;
+#[cfg(feature = "serde")]
+mod serde_impls;
+
+pub use cursor::Cursor;
+
+#[derive(Clone)]
 pub struct SparseVec<T> {
+    /// The length, or a lower bound from the end of the loaded data while the length is unknown
     len: usize,
+    len_known: bool,
     /// Each block starts from an offset within the SparseVec range and proceeds to the end of it's Vec
-    blocks: Vec<(usize, Vec<T>)>,
+    blocks: Blocks<T>,
+}
+
+/// The list of blocks, with the `smallvec` feature the first few are held inline rather than allocated
+#[cfg(feature = "smallvec")]
+type Blocks<T> = smallvec::SmallVec<[(usize, Vec<T>); 4]>;
+#[cfg(not(feature = "smallvec"))]
+type Blocks<T> = Vec<(usize, Vec<T>)>;
+
+impl<T> SparseVec<T> {
+    pub fn with_len(len: usize) -> Self {
+        SparseVec {
+            len,
+            len_known: true,
+            blocks: Blocks::new(),
+        }
+    }
+
+    /// Construct without knowing the length, data can be inserted at any index and the length is a lower bound which grows as it is
+    pub fn with_unknown_len() -> Self {
+        SparseVec {
+            len: 0,
+            len_known: false,
+            blocks: Blocks::new(),
+        }
+    }
+
+    /// Construct from `(offset, items)` blocks, which must be within the length and must not overlap
+    pub fn from_blocks(
+        len: usize,
+        blocks: impl IntoIterator<Item = (usize, Vec<T>)>,
+    ) -> Result<Self, InsertError> {
+        let mut vec = SparseVec::with_len(len);
+        for (offset, block) in blocks {
+            vec.try_insert_vec(offset, block)?;
+        }
+        Ok(vec)
+    }
+
+    /// The length, or while the length is unknown, a lower bound from the end of the loaded data
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// The length if it is known
+    pub fn known_len(&self) -> Option<usize> {
+        if self.len_known {
+            Some(self.len)
+        } else {
+            None
+        }
+    }
+
+    /// The end of the space that data can be inserted into
+    fn insert_bound(&self) -> usize {
+        self.known_len().unwrap_or(usize::MAX)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Get the item at `idx` if it is loaded
+    pub fn get(&self, idx: usize) -> Option<&T> {
+        let (offset, vec) = &self.blocks[block_containing(&self.blocks, idx)?];
+        vec.get(idx - offset)
+    }
+
+    /// Get the items at each of the indices if they are loaded, in the order of the indices, resolving them in one pass over the blocks
+    pub fn get_many(&self, idxs: &[usize]) -> Vec<Option<&T>> {
+        let mut order: Vec<usize> = (0..idxs.len()).collect();
+        order.sort_unstable_by_key(|i| idxs[*i]);
+        let mut items = vec![None; idxs.len()];
+        let mut blocks = self.blocks.iter().peekable();
+        for i in order {
+            let idx = idxs[i];
+            while blocks
+                .next_if(|(offset, vec)| offset + vec.len() <= idx)
+                .is_some()
+            {}
+            items[i] = blocks
+                .peek()
+                .and_then(|(offset, vec)| vec.get(idx.checked_sub(*offset)?));
+        }
+        items
+    }
+
+    /// Get the item at `idx` mutably if it is loaded
+    pub fn get_mut(&mut self, idx: usize) -> Option<&mut T> {
+        let pos = block_containing(&self.blocks, idx)?;
+        let (offset, vec) = &mut self.blocks[pos];
+        vec.get_mut(idx - *offset)
+    }
+
+    /// The items in the range as a slice, if they are all loaded and held by a single block
+    pub fn as_contiguous_slice(&self, idxs: Range<usize>) -> Option<&[T]> {
+        contiguous_slice(&self.blocks, self.len, idxs)
+    }
+
+    /// Whether the item at `idx` is loaded
+    pub fn contains(&self, idx: usize) -> bool {
+        block_containing(&self.blocks, idx).is_some()
+    }
+
+    /// Convert range bounds to a range clamped to the length
+    pub(crate) fn clamp_range(&self, idxs: impl RangeBounds<usize>) -> Range<usize> {
+        clamp_range(self.len, idxs)
+    }
+
+    pub fn iter_range(&self, idxs: impl RangeBounds<usize>) -> Iter<'_, T> {
+        Iter::new(&self.blocks, self.clamp_range(idxs))
+    }
+
+    /// Iterate over the range in descending index order, walking the blocks from the high end
+    pub fn iter_range_rev(&self, idxs: impl RangeBounds<usize>) -> Rev<Iter<'_, T>> {
+        self.iter_range(idxs).rev()
+    }
+
+    /// Iterate over the loaded blocks as slices along with the range they occupy
+    pub fn iter_blocks(&self) -> impl DoubleEndedIterator<Item = (Range<usize>, &[T])> + '_ {
+        self.iter_blocks_in(0..self.len)
+    }
+
+    /// Iterate over the loaded blocks as slices along with the range they occupy, clipped to `idxs`
+    pub fn iter_blocks_in(
+        &self,
+        idxs: impl RangeBounds<usize>,
+    ) -> impl DoubleEndedIterator<Item = (Range<usize>, &[T])> + '_ {
+        blocks_in(&self.blocks, self.clamp_range(idxs))
+    }
+
+    /// Iterate in parallel over the loaded blocks as slices along with the range they occupy
+    #[cfg(feature = "rayon")]
+    pub fn par_iter_blocks(
+        &self,
+    ) -> impl rayon::iter::IndexedParallelIterator<Item = (Range<usize>, &[T])> + '_
+    where
+        T: Sync,
+    {
+        use rayon::prelude::*;
+        self.blocks
+            .par_iter()
+            .map(|(offset, vec)| (*offset..(offset + vec.len()), &vec[..]))
+    }
+
+    /// Iterate over only the loaded items along with their indices
+    pub fn iter_present(&self) -> impl DoubleEndedIterator<Item = (usize, &T)> + '_ {
+        self.blocks.iter().flat_map(|(offset, vec)| {
+            vec.iter()
+                .enumerate()
+                .map(move |(i, item)| (offset + i, item))
+        })
+    }
+
+    pub fn iter_range_mut(&mut self, idxs: impl RangeBounds<usize>) -> IterMut<'_, T> {
+        let idxs = self.clamp_range(idxs);
+        // binary search for the first block which ends after the start
+        let first = self
+            .blocks
+            .partition_point(|(offset, vec)| offset + vec.len() <= idxs.start);
+        let mut blocks_iter = self.blocks[first..].iter_mut();
+        let block_iter = blocks_iter.next().map(|(offset, vec)| {
+            (
+                *offset,
+                vec[idxs.start.saturating_sub(*offset)..].iter_mut(),
+            )
+        });
+        IterMut {
+            len: idxs.end,
+            position: idxs.start,
+            blocks_iter,
+            block_iter,
+        }
+    }
+
+    /// A cursor at `idx`, clamped to the length, for navigating nearby indices without searching the blocks each time
+    pub fn cursor(&self, idx: usize) -> Cursor<'_, T> {
+        Cursor::new(self, idx)
+    }
+
+    /// The lowest loaded index
+    pub fn first_loaded(&self) -> Option<usize> {
+        self.blocks
+            .iter()
+            .find(|(_, vec)| !vec.is_empty())
+            .map(|(offset, _)| *offset)
+    }
+
+    /// The highest loaded index
+    pub fn last_loaded(&self) -> Option<usize> {
+        self.blocks
+            .iter()
+            .rev()
+            .find(|(_, vec)| !vec.is_empty())
+            .map(|(offset, vec)| offset + vec.len() - 1)
+    }
+
+    /// The loaded index closest to `idx`, preferring the lower index if two are as close
+    pub fn nearest_loaded(&self, idx: usize) -> Option<usize> {
+        let pos = self.blocks.partition_point(|(offset, _)| *offset <= idx);
+        let before = self.blocks[..pos]
+            .iter()
+            .rev()
+            .find(|(_, vec)| !vec.is_empty())
+            .map(|(offset, vec)| (offset + vec.len() - 1).min(idx));
+        let after = self.blocks[pos..]
+            .iter()
+            .find(|(_, vec)| !vec.is_empty())
+            .map(|(offset, _)| *offset);
+        match (before, after) {
+            (Some(before), Some(after)) if after - idx < idx - before => Some(after),
+            (Some(before), _) => Some(before),
+            (None, after) => after,
+        }
+    }
+
+    /// Iterate over the range as alternating runs of loaded items and unloaded ranges, clamped to the length, adjacent blocks are separate runs
+    pub fn iter_runs(&self, idxs: impl RangeBounds<usize>) -> Runs<'_, T> {
+        Runs::new(&self.blocks, self.clamp_range(idxs))
+    }
+
+    /// The ranges which are not loaded
+    pub fn gaps(&self) -> Gaps<'_, T> {
+        self.gaps_in(0..self.len)
+    }
+
+    /// The ranges within `idxs` which are not loaded, clamped to the length
+    pub fn gaps_in(&self, idxs: impl RangeBounds<usize>) -> Gaps<'_, T> {
+        Gaps::new(&self.blocks, self.clamp_range(idxs))
+    }
+
+    /// The first unloaded range which starts at or after `idx`, or which `idx` is in
+    // Binary searches for the block at `idx`, then walks only any adjacent blocks after it
+    pub fn first_gap_at_or_after(&self, idx: usize) -> Option<Range<usize>> {
+        self.gaps_in(idx..).next()
+    }
+
+    /// Whether every item in the range is loaded, ranges extending past the end are never loaded
+    pub fn is_range_loaded(&self, idxs: Range<usize>) -> bool {
+        idxs.end <= self.len && self.gaps_in(idxs).next().is_none()
+    }
+
+    /// The contiguous ranges which are loaded, adjacent blocks are reported as one range
+    pub fn loaded_ranges(&self) -> LoadedRanges<'_, T> {
+        LoadedRanges::new(&self.blocks)
+    }
+
+    pub fn iter(&self) -> Iter<'_, T> {
+        self.iter_range(0..self.len)
+    }
+
+    /// Clone all of the items in the range onto the end of `out`, copying a slice from each block, or leave `out` unchanged if any are not loaded
+    pub fn copy_range_into(&self, idxs: Range<usize>, out: &mut Vec<T>) -> Result<(), GapError>
+    where
+        T: Clone,
+    {
+        if idxs.end > self.len {
+            return Err(GapError(idxs.start.max(self.len)..idxs.end));
+        }
+        if let Some(gap) = self.gaps_in(idxs.clone()).next() {
+            return Err(GapError(gap));
+        }
+        self.extend_from_range(idxs, out);
+        Ok(())
+    }
+
+    /// Clone the loaded items in the range onto the end of `out`, skipping any which are not loaded, and return how many were cloned
+    pub fn extend_from_range(&self, idxs: impl RangeBounds<usize>, out: &mut Vec<T>) -> usize
+    where
+        T: Clone,
+    {
+        let before = out.len();
+        for (_, block) in self.iter_blocks_in(idxs) {
+            out.extend_from_slice(block);
+        }
+        out.len() - before
+    }
+
+    /// The ranges which are loaded in both this and `other`
+    pub fn loaded_intersection<U>(&self, other: &SparseVec<U>) -> Vec<Range<usize>> {
+        let mut others = other.loaded_ranges().peekable();
+        let mut intersection = vec![];
+        for range in self.loaded_ranges() {
+            while let Some(other) = others.peek() {
+                let overlap = range.start.max(other.start)..range.end.min(other.end);
+                if !overlap.is_empty() {
+                    intersection.push(overlap);
+                }
+                if other.end > range.end {
+                    break;
+                }
+                others.next();
+            }
+        }
+        intersection
+    }
+
+    /// The ranges which are loaded in `other` but not in this, that is which `other` could fill in
+    pub fn missing_relative_to<U>(&self, other: &SparseVec<U>) -> Vec<Range<usize>> {
+        other
+            .loaded_ranges()
+            .flat_map(|range| self.gaps_in(range))
+            .collect()
+    }
+
+    /// Insert data into empty space
+    // Panics if space is occupied or the data extends past the end
+    pub fn insert_vec(&mut self, start: usize, vec: Vec<T>) {
+        if let Err(err) = self.try_insert_vec(start, vec) {
+            panic!("{}", err);
+        }
+    }
+
+    /// Insert data into empty space, or leave the SparseVec unchanged if the space is occupied or the data extends past the end
+    pub fn try_insert_vec(&mut self, start: usize, vec: Vec<T>) -> Result<(), InsertError> {
+        let insert_pos = insert_position(&self.blocks, self.insert_bound(), start, vec.len())?;
+        self.len = self.len.max(start + vec.len());
+        if !vec.is_empty() {
+            self.blocks.insert(insert_pos, (start, vec));
+        }
+        Ok(())
+    }
+
+    /// Insert data, replacing any which is already loaded in its range
+    // Panics if the data extends past the end
+    pub fn insert_vec_overwrite(&mut self, start: usize, vec: Vec<T>) {
+        let end = start.saturating_add(vec.len());
+        if end > self.insert_bound() {
+            panic!(
+                "{}",
+                InsertError::OutOfBounds {
+                    start,
+                    len: vec.len()
+                }
+            );
+        }
+        self.remove_range(start..end);
+        self.insert_vec(start, vec);
+    }
+
+    /// Set the item at `idx`, returning the previous item if it was loaded, an unloaded item is appended to an adjacent block if there is one
+    // Panics if `idx` is past the end
+    pub fn set(&mut self, idx: usize, value: T) -> Option<T> {
+        if let Some(item) = self.get_mut(idx) {
+            return Some(mem::replace(item, value));
+        }
+        assert!(
+            idx < self.insert_bound(),
+            "{}",
+            InsertError::OutOfBounds { start: idx, len: 1 }
+        );
+        self.len = self.len.max(idx + 1);
+        let pos = self.blocks.partition_point(|(offset, _)| *offset < idx);
+        match pos.checked_sub(1).map(|prev| &mut self.blocks[prev]) {
+            Some((offset, prev)) if *offset + prev.len() == idx => prev.push(value),
+            _ => match self.blocks.get_mut(pos) {
+                Some((offset, next)) if *offset == idx + 1 => {
+                    next.insert(0, value);
+                    *offset = idx;
+                }
+                _ => self.blocks.insert(pos, (idx, vec![value])),
+            },
+        }
+        None
+    }
+
+    /// Load every unloaded item in the range with a clone of `value`, leaving any already loaded items as they are
+    // Panics if the range extends past the end
+    pub fn fill(&mut self, idxs: Range<usize>, value: T)
+    where
+        T: Clone,
+    {
+        if !self.len_known && !idxs.is_empty() {
+            self.len = self.len.max(idxs.end);
+        }
+        assert!(
+            idxs.end <= self.len,
+            "Filled range {:?} extends past the end",
+            idxs
+        );
+        let gaps: Vec<_> = self.gaps_in(idxs).collect();
+        for gap in gaps {
+            self.insert_vec(gap.start, vec![value.clone(); gap.len()]);
+        }
+    }
+
+    /// Insert the loaded blocks of `other`, resolving items loaded in both according to the policy
+    /// With `MergePolicy::Error`, or if any of `other`'s blocks extend past the end, this SparseVec is left unchanged
+    pub fn merge(&mut self, other: SparseVec<T>, policy: MergePolicy) -> Result<(), InsertError> {
+        for (offset, vec) in &other.blocks {
+            if policy == MergePolicy::Error {
+                insert_position(&self.blocks, self.insert_bound(), *offset, vec.len())?;
+            } else if offset.saturating_add(vec.len()) > self.insert_bound() {
+                return Err(InsertError::OutOfBounds {
+                    start: *offset,
+                    len: vec.len(),
+                });
+            }
+        }
+        for (offset, mut vec) in other.blocks {
+            match policy {
+                MergePolicy::PreferSelf => {
+                    // against the insert bound, as the length of data of unknown length is only a lower bound
+                    let idxs = clamp_range(self.insert_bound(), offset..(offset + vec.len()));
+                    let gaps: Vec<_> = Gaps::new(&self.blocks, idxs).collect();
+                    for gap in gaps.into_iter().rev() {
+                        let mut tail = vec.split_off(gap.start - offset);
+                        tail.truncate(gap.len());
+                        self.insert_vec(gap.start, tail);
+                    }
+                }
+                MergePolicy::PreferOther => self.insert_vec_overwrite(offset, vec),
+                MergePolicy::Error => self.insert_vec(offset, vec),
+            }
+        }
+        Ok(())
+    }
+
+    /// Change the length, growing with unloaded space or evicting any loaded data past the new end, the length is then known
+    pub fn set_len(&mut self, len: usize) {
+        if len < self.len {
+            self.remove_range(len..self.len);
+        }
+        self.len = len;
+        self.len_known = true;
+    }
+
+    /// Insert loaded items at `at`, shifting everything after it along and growing the length
+    // Panics if `at` is past the end
+    pub fn splice_insert(&mut self, at: usize, items: Vec<T>) {
+        assert!(at <= self.len, "Splice position {} is past the end", at);
+        self.split_block_at(at);
+        let pos = self.blocks.partition_point(|(offset, _)| *offset < at);
+        for (offset, _) in &mut self.blocks[pos..] {
+            *offset += items.len();
+        }
+        self.len += items.len();
+        if !items.is_empty() {
+            self.blocks.insert(pos, (at, items));
+        }
+    }
+
+    /// Remove the range, evicting any loaded data in it, shifting everything after it back and shrinking the length
+    // Panics if the range extends past the end
+    pub fn remove_shifting(&mut self, range: Range<usize>) {
+        assert!(
+            range.end <= self.len,
+            "Removed range {:?} extends past the end",
+            range
+        );
+        self.remove_range(range.clone());
+        let removed = range.end.saturating_sub(range.start);
+        let pos = self
+            .blocks
+            .partition_point(|(offset, _)| *offset < range.end);
+        for (offset, _) in &mut self.blocks[pos..] {
+            *offset -= removed;
+        }
+        self.len -= removed;
+    }
+
+    /// Split into the items before `idx` and the items from `idx`, splitting any block which straddles it
+    /// The second SparseVec is indexed from 0 and its length is unknown if this one's was
+    // Panics if `idx` is past the end
+    pub fn split_at(mut self, idx: usize) -> (SparseVec<T>, SparseVec<T>) {
+        assert!(idx <= self.len, "Split position {} is past the end", idx);
+        self.split_block_at(idx);
+        let pos = self.blocks.partition_point(|(offset, _)| *offset < idx);
+        let tail = self
+            .blocks
+            .drain(pos..)
+            .map(|(offset, vec)| (offset - idx, vec))
+            .collect();
+        let after = SparseVec {
+            len: self.len - idx,
+            len_known: self.len_known,
+            blocks: tail,
+        };
+        let before = SparseVec {
+            len: idx,
+            len_known: true,
+            blocks: self.blocks,
+        };
+        (before, after)
+    }
+
+    /// Add the items of `other` after the end, the length is then unknown if `other`'s was
+    // Panics if the length of this SparseVec is unknown
+    pub fn append(&mut self, other: SparseVec<T>) {
+        assert!(self.len_known, "Can't append after an unknown length");
+        let shift = self.len;
+        self.blocks.extend(
+            other
+                .blocks
+                .into_iter()
+                .map(|(offset, vec)| (offset + shift, vec)),
+        );
+        self.len += other.len;
+        self.len_known = other.len_known;
+    }
+
+    /// Convert each loaded item, keeping them at the same indices
+    pub fn map<U>(self, mut f: impl FnMut(T) -> U) -> SparseVec<U> {
+        SparseVec {
+            len: self.len,
+            len_known: self.len_known,
+            blocks: self
+                .blocks
+                .into_iter()
+                .map(|(offset, vec)| (offset, vec.into_iter().map(&mut f).collect()))
+                .collect(),
+        }
+    }
+
+    /// Convert each loaded item by reference, keeping them at the same indices
+    pub fn map_ref<U>(&self, mut f: impl FnMut(&T) -> U) -> SparseVec<U> {
+        SparseVec {
+            len: self.len,
+            len_known: self.len_known,
+            blocks: self
+                .blocks
+                .iter()
+                .map(|(offset, vec)| (*offset, vec.iter().map(&mut f).collect()))
+                .collect(),
+        }
+    }
+
+    /// Evict the loaded items for which the predicate returns false, leaving gaps where they were
+    pub fn retain(&mut self, mut f: impl FnMut(usize, &T) -> bool) {
+        let mut retained = Blocks::with_capacity(self.blocks.len());
+        for (offset, vec) in self.blocks.drain(..) {
+            let mut run: Option<(usize, Vec<T>)> = None;
+            for (idx, item) in (offset..).zip(vec) {
+                if !f(idx, &item) {
+                    retained.extend(run.take());
+                } else if let Some((_, items)) = &mut run {
+                    items.push(item);
+                } else {
+                    run = Some((idx, vec![item]));
+                }
+            }
+            retained.extend(run);
+        }
+        self.blocks = retained;
+    }
+
+    /// Merge adjacent blocks into single blocks, so that there is one block per loaded range
+    pub fn defragment(&mut self) {
+        let mut merged = Blocks::with_capacity(self.loaded_ranges().count());
+        for (offset, mut vec) in self.blocks.drain(..) {
+            match merged.last_mut() {
+                Some((prev_offset, prev)) if *prev_offset + prev.len() == offset => {
+                    prev.append(&mut vec)
+                }
+                _ => merged.push((offset, vec)),
+            }
+        }
+        self.blocks = merged;
+    }
+
+    /// Count the loaded items and blocks and estimate the heap memory they use
+    pub fn memory_usage(&self) -> MemoryUsage {
+        MemoryUsage {
+            items: self.blocks.iter().map(|(_, vec)| vec.len()).sum(),
+            blocks: self.blocks.len(),
+            heap_bytes: self.blocks_heap_capacity() * mem::size_of::<(usize, Vec<T>)>()
+                + self
+                    .blocks
+                    .iter()
+                    .map(|(_, vec)| vec.capacity() * mem::size_of::<T>())
+                    .sum::<usize>(),
+        }
+    }
+
+    /// Release excess capacity of the blocks and the block list, returning the estimated heap bytes reclaimed
+    pub fn shrink_to_fit(&mut self) -> usize {
+        let before = self.memory_usage().heap_bytes;
+        for (_, vec) in &mut self.blocks {
+            vec.shrink_to_fit();
+        }
+        self.blocks.shrink_to_fit();
+        before - self.memory_usage().heap_bytes
+    }
+
+    /// Draw which parts are loaded, with each character covering an equal share of the indices
+    /// `█` is fully loaded, `▒` partly loaded and `░` not loaded, the width is clamped to the length
+    pub fn coverage_string(&self, width: usize) -> String {
+        let width = width.min(self.len);
+        (0..width)
+            .map(|i| {
+                let bucket_start =
+                    |i: usize| (i as u128 * self.len as u128 / width as u128) as usize;
+                let bucket = bucket_start(i)..bucket_start(i + 1);
+                let loaded: usize = self
+                    .iter_blocks_in(bucket.clone())
+                    .map(|(range, _)| range.len())
+                    .sum();
+                if loaded == bucket.len() {
+                    '█'
+                } else if loaded == 0 {
+                    '░'
+                } else {
+                    '▒'
+                }
+            })
+            .collect()
+    }
+
+    /// Summarise how much of the SparseVec is loaded
+    pub fn stats(&self) -> Stats {
+        let loaded_items = self.blocks.iter().map(|(_, vec)| vec.len()).sum();
+        let (gaps, largest_gap) = self.gaps().fold((0, 0), |(count, largest), gap| {
+            (count + 1, largest.max(gap.len()))
+        });
+        Stats {
+            loaded_items,
+            blocks: self.blocks.len(),
+            gaps,
+            largest_gap,
+            coverage: if self.len == 0 {
+                1.
+            } else {
+                loaded_items as f64 / self.len as f64
+            },
+        }
+    }
+
+    /// Take the loaded blocks as `(offset, items)` pairs in order
+    pub fn into_blocks(self) -> Vec<(usize, Vec<T>)> {
+        self.blocks.into_iter().collect()
+    }
+
+    /// Evict all loaded data, keeping the length
+    pub fn clear(&mut self) {
+        self.blocks.clear();
+    }
+
+    /// Evict all loaded data and change the length, the length is then known
+    pub fn reset(&mut self, len: usize) {
+        self.blocks.clear();
+        self.len = len;
+        self.len_known = true;
+    }
+
+    /// Evict any loaded data in the range, splitting blocks which straddle its ends
+    pub fn remove_range(&mut self, range: Range<usize>) {
+        self.drain_blocks(range).for_each(drop);
+    }
+
+    /// Remove and return the items in the range, clamped to the length, with `None` for those which were not loaded
+    pub fn drain_range(&mut self, idxs: impl RangeBounds<usize>) -> Vec<Option<T>> {
+        let idxs = self.clamp_range(idxs);
+        let mut drained = Vec::with_capacity(idxs.len());
+        for (offset, vec) in self.drain_blocks(idxs.clone()) {
+            drained.resize_with(offset - idxs.start, || None);
+            drained.extend(vec.into_iter().map(Some));
+        }
+        drained.resize_with(idxs.len(), || None);
+        drained
+    }
+
+    /// Evict all loaded data outside the range, trimming blocks which straddle its ends
+    pub fn evict_outside(&mut self, keep: Range<usize>) {
+        self.remove_range(0..keep.start);
+        self.remove_range(keep.end..self.len);
+    }
+
+    /// Evict all loaded data outside of every one of the ranges, use this to keep pinned ranges along with the window around the view
+    pub fn evict_outside_ranges(&mut self, keep: &[Range<usize>]) {
+        for range in complement(keep, self.len) {
+            self.remove_range(range);
+        }
+    }
+
+    /// Remove and return the blocks in the range, after splitting so that they are fully contained by it
+    fn drain_blocks(&mut self, range: Range<usize>) -> impl Iterator<Item = (usize, Vec<T>)> + '_ {
+        if range.is_empty() {
+            return self.blocks.drain(0..0);
+        }
+        self.split_block_at(range.start);
+        self.split_block_at(range.end);
+        let first = self
+            .blocks
+            .partition_point(|(offset, _)| *offset < range.start);
+        let last = self
+            .blocks
+            .partition_point(|(offset, _)| *offset < range.end);
+        self.blocks.drain(first..last)
+    }
+
+    /// The number of blocks the block list has allocated heap space for
+    fn blocks_heap_capacity(&self) -> usize {
+        #[cfg(feature = "smallvec")]
+        if !self.blocks.spilled() {
+            return 0;
+        }
+        self.blocks.capacity()
+    }
+
+    /// Ensure that there is a block boundary at `idx` by splitting the block which holds it
+    fn split_block_at(&mut self, idx: usize) {
+        if let Some(pos) = block_containing(&self.blocks, idx) {
+            let (offset, vec) = &mut self.blocks[pos];
+            if idx > *offset {
+                let tail = vec.split_off(idx - *offset);
+                self.blocks.insert(pos + 1, (idx, tail));
+            }
+        }
+    }
+}
+
+/// Binary search for the position of the block which holds `idx`
+pub(crate) fn block_containing<T, B: AsRef<[T]>>(
+    blocks: &[(usize, B)],
+    idx: usize,
+) -> Option<usize> {
+    let pos = blocks
+        .partition_point(|(offset, _)| *offset <= idx)
+        .checked_sub(1)?;
+    let (offset, block) = &blocks[pos];
+    if idx < offset + block.as_ref().len() {
+        Some(pos)
+    } else {
+        None
+    }
+}
+
+/// The blocks which hold any items in the range
+fn blocks_overlapping<'b, T, B: AsRef<[T]>>(
+    blocks: &'b [(usize, B)],
+    idxs: &Range<usize>,
+) -> &'b [(usize, B)] {
+    let first =
+        blocks.partition_point(|(offset, block)| offset + block.as_ref().len() <= idxs.start);
+    let last = blocks
+        .partition_point(|(offset, _)| *offset < idxs.end)
+        .max(first);
+    &blocks[first..last]
+}
+
+/// The blocks which hold any items in the range as slices along with the range they occupy, clipped to the range
+pub(crate) fn blocks_in<'b, T: 'b, B: AsRef<[T]>>(
+    blocks: &'b [(usize, B)],
+    idxs: Range<usize>,
+) -> impl DoubleEndedIterator<Item = (Range<usize>, &'b [T])> + 'b {
+    blocks_overlapping(blocks, &idxs)
+        .iter()
+        .map(move |(offset, block)| {
+            let block = block.as_ref();
+            let start = idxs.start.max(*offset);
+            let end = idxs.end.min(offset + block.len());
+            (start..end, &block[(start - offset)..(end - offset)])
+        })
+}
+
+/// The items in the range as a slice, if they are all loaded and held by a single block
+pub(crate) fn contiguous_slice<T, B: AsRef<[T]>>(
+    blocks: &[(usize, B)],
+    len: usize,
+    idxs: Range<usize>,
+) -> Option<&[T]> {
+    if idxs.is_empty() {
+        return if idxs.start <= len { Some(&[]) } else { None };
+    }
+    let (offset, block) = &blocks[block_containing(blocks, idxs.start)?];
+    block
+        .as_ref()
+        .get((idxs.start - offset)..(idxs.end - offset))
+}
+
+/// The ranges within `0..len` which aren't in any of `ranges`, in order
+pub(crate) fn complement(ranges: &[Range<usize>], len: usize) -> Vec<Range<usize>> {
+    let mut ranges = ranges.to_vec();
+    ranges.sort_unstable_by_key(|range| range.start);
+    let mut outside = vec![];
+    let mut pos = 0;
+    for range in ranges {
+        if pos < range.start {
+            outside.push(pos..range.start.min(len));
+        }
+        pos = pos.max(range.end);
+    }
+    if pos < len {
+        outside.push(pos..len);
+    }
+    outside.retain(|range| !range.is_empty());
+    outside
+}
+
+/// Convert range bounds to a range clamped to the length
+pub(crate) fn clamp_range(len: usize, idxs: impl RangeBounds<usize>) -> Range<usize> {
+    let end = match idxs.end_bound() {
+        Bound::Included(end) => end.saturating_add(1),
+        Bound::Excluded(end) => *end,
+        Bound::Unbounded => len,
+    }
+    .min(len);
+    let start = match idxs.start_bound() {
+        Bound::Included(start) => *start,
+        Bound::Excluded(start) => start.saturating_add(1),
+        Bound::Unbounded => 0,
+    }
+    .min(end);
+    start..end
+}
+
+/// Find where a block of `block_len` items at `start` should be inserted, checking that it is in empty space
+pub(crate) fn insert_position<T, B: AsRef<[T]>>(
+    blocks: &[(usize, B)],
+    len: usize,
+    start: usize,
+    block_len: usize,
+) -> Result<usize, InsertError> {
+    let end = start
+        .checked_add(block_len)
+        .filter(|end| *end <= len)
+        .ok_or(InsertError::OutOfBounds {
+            start,
+            len: block_len,
+        })?;
+    let insert_pos = blocks.partition_point(|(offset, _)| *offset < start);
+    if block_len == 0 {
+        return Ok(insert_pos);
+    }
+    if let Some((offset, prev)) = insert_pos.checked_sub(1).map(|pos| &blocks[pos]) {
+        let prev_end = offset + prev.as_ref().len();
+        if prev_end > start {
+            return Err(InsertError::Overlap(*offset..prev_end));
+        }
+    }
+    if let Some((offset, next)) = blocks.get(insert_pos) {
+        if *offset < end {
+            return Err(InsertError::Overlap(
+                *offset..(offset + next.as_ref().len()),
+            ));
+        }
+    }
+    Ok(insert_pos)
+}
+
+/// Shows the coverage and the loaded blocks by offset
+impl<T: fmt::Debug> fmt::Debug for SparseVec<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SparseVec")
+            .field("len", &self.len)
+            .field("len_known", &self.len_known)
+            .field("coverage", &self.coverage_string(64))
+            .field("blocks", &DebugBlocks(&self.blocks))
+            .finish()
+    }
+}
+
+struct DebugBlocks<'b, T>(&'b [(usize, Vec<T>)]);
+
+impl<'b, T: fmt::Debug> fmt::Debug for DebugBlocks<'b, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map()
+            .entries(self.0.iter().map(|(offset, vec)| (offset, vec)))
+            .finish()
+    }
+}
+
+/// SparseVecs are equal if they have the same length and the same items loaded at the same indices, however they are split into blocks
+impl<T: PartialEq> PartialEq for SparseVec<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.known_len() == other.known_len()
+            && self.len == other.len
+            && self.iter_present().eq(other.iter_present())
+    }
+}
+
+impl<T: Eq> Eq for SparseVec<T> {}
+
+impl<T> Index<usize> for SparseVec<T> {
+    type Output = T;
+
+    // Panics if the item is not loaded
+    fn index(&self, idx: usize) -> &T {
+        self.get(idx)
+            .unwrap_or_else(|| panic!("Index {} is not loaded", idx))
+    }
+}
+
+impl<T> Index<Range<usize>> for SparseVec<T> {
+    type Output = [T];
+
+    // Panics if the items are not all loaded in the same block
+    fn index(&self, idxs: Range<usize>) -> &[T] {
+        self.as_contiguous_slice(idxs.clone())
+            .unwrap_or_else(|| panic!("Range {:?} is not loaded in a single block", idxs))
+    }
+}
+
+/// Memory used by the loaded data of a SparseVec
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MemoryUsage {
+    /// Number of loaded items
+    pub items: usize,
+    /// Number of blocks the items are stored in
+    pub blocks: usize,
+    /// Approximate heap bytes used by the blocks, not including any heap data owned by the items themselves
+    pub heap_bytes: usize,
+}
+
+/// Summary of the loaded data of a SparseVec
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Stats {
+    /// Number of loaded items
+    pub loaded_items: usize,
+    /// Number of blocks the items are stored in
+    pub blocks: usize,
+    /// Number of unloaded ranges
+    pub gaps: usize,
+    /// Length of the longest unloaded range
+    pub largest_gap: usize,
+    /// Fraction of the items which are loaded, an empty SparseVec is fully loaded
+    pub coverage: f64,
+}
+
+/// Error reading a range which is not fully loaded, holding the first unloaded range within it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GapError(pub Range<usize>);
+
+impl fmt::Display for GapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Range {:?} is not loaded", self.0)
+    }
+}
+
+impl Error for GapError {}
+
+/// How `SparseVec::merge` resolves items which are loaded in both SparseVecs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergePolicy {
+    /// Keep the items already loaded
+    PreferSelf,
+    /// Replace the items already loaded with those being merged in
+    PreferOther,
+    /// Fail with `InsertError::Overlap`
+    Error,
+}
+
+/// Error inserting into a SparseVec, `I` is the type used for indices
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InsertError<I = usize> {
+    /// The inserted data overlaps the loaded block with this range
+    Overlap(Range<I>),
+    /// The inserted data would extend past the end of the SparseVec
+    OutOfBounds { start: I, len: usize },
+}
+
+impl InsertError {
+    /// Convert the indices in the error
+    pub(crate) fn map_index<I>(self, f: impl Fn(usize) -> I) -> InsertError<I> {
+        match self {
+            InsertError::Overlap(block) => InsertError::Overlap(f(block.start)..f(block.end)),
+            InsertError::OutOfBounds { start, len } => InsertError::OutOfBounds {
+                start: f(start),
+                len,
+            },
+        }
+    }
+}
+
+impl<I: fmt::Debug + fmt::Display> fmt::Display for InsertError<I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InsertError::Overlap(block) => {
+                write!(f, "Inserted vec overlaps existing block {:?}", block)
+            }
+            InsertError::OutOfBounds { start, len } => write!(
+                f,
+                "Inserted vec of length {} at {} extends past the end",
+                len, start
+            ),
+        }
+    }
+}
+
+impl<I: fmt::Debug + fmt::Display> Error for InsertError<I> {}
+
+impl<T> From<Vec<T>> for SparseVec<T> {
+    fn from(vec: Vec<T>) -> Self {
+        let mut blocks = Blocks::new();
+        let len = vec.len();
+        if len > 0 {
+            blocks.push((0, vec));
+        }
+        Self {
+            len,
+            len_known: true,
+            blocks,
+        }
+    }
+}
+
+impl<T> FromIterator<Option<T>> for SparseVec<T> {
+    fn from_iter<I: IntoIterator<Item = Option<T>>>(iter: I) -> Self {
+        let mut vec = SparseVec::with_len(0);
+        for item in iter {
+            if let Some(item) = item {
+                match vec.blocks.last_mut() {
+                    Some((offset, block)) if *offset + block.len() == vec.len => block.push(item),
+                    _ => vec.blocks.push((vec.len, vec![item])),
+                }
+            }
+            vec.len += 1;
+        }
+        vec
+    }
+}
+
+impl<T> IntoIterator for SparseVec<T> {
+    type Item = Option<T>;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> IntoIter<T> {
+        IntoIter {
+            len: self.len,
+            position: 0,
+            blocks_iter: self.blocks.into_iter(),
+            block_iter: None,
+        }
+    }
+}
+
+impl<'i, T> IntoIterator for &'i SparseVec<T> {
+    type Item = Option<&'i T>;
+    type IntoIter = Iter<'i, T>;
+
+    fn into_iter(self) -> Iter<'i, T> {
+        self.iter()
+    }
+}
+
+pub struct Iter<'i, T, B = Vec<T>> {
+    /// where the iteration ends, and where the next iteration from the back will come before
+    end: usize,
+    /// where the next iteration will come from
+    position: usize,
+    /// the blocks which may hold items in the remaining range
+    blocks: &'i [(usize, B)],
+    item: PhantomData<&'i T>,
+}
+
+impl<'i, T, B: AsRef<[T]>> Iter<'i, T, B> {
+    pub(crate) fn new(blocks: &'i [(usize, B)], idxs: Range<usize>) -> Self {
+        Iter {
+            blocks: blocks_overlapping(blocks, &idxs),
+            end: idxs.end,
+            position: idxs.start,
+            item: PhantomData,
+        }
+    }
+}
+
+impl<'i, T, B: AsRef<[T]>> Iterator for Iter<'i, T, B> {
+    type Item = Option<&'i T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.position >= self.end {
+            return None;
+        }
+        // discard blocks that end before the position
+        while let Some(((offset, block), rest)) = self.blocks.split_first() {
+            if offset + block.as_ref().len() > self.position {
+                break;
+            }
+            self.blocks = rest;
+        }
+        let position = self.position;
+        self.position += 1;
+        Some(
+            self.blocks
+                .first()
+                .and_then(|(offset, block)| block.as_ref().get(position.checked_sub(*offset)?)),
+        )
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.end.saturating_sub(self.position);
+        (len, Some(len))
+    }
+}
+
+impl<'i, T, B: AsRef<[T]>> ExactSizeIterator for Iter<'i, T, B> {}
+
+impl<'i, T, B: AsRef<[T]>> FusedIterator for Iter<'i, T, B> {}
+
+impl<'i, T, B: AsRef<[T]>> DoubleEndedIterator for Iter<'i, T, B> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.position >= self.end {
+            return None;
+        }
+        self.end -= 1;
+        // discard blocks that start after the end
+        while let Some(((offset, _), rest)) = self.blocks.split_last() {
+            if *offset <= self.end {
+                break;
+            }
+            self.blocks = rest;
+        }
+        Some(
+            self.blocks
+                .last()
+                .and_then(|(offset, block)| block.as_ref().get(self.end - offset)),
+        )
+    }
+}
+
+pub struct IterMut<'i, T> {
+    /// where the iteration ends
+    len: usize,
+    /// where the next iteration will come from
+    position: usize,
+    /// the remaining blocks to be iterated over
+    blocks_iter: slice::IterMut<'i, (usize, Vec<T>)>,
+    /// the current block being iterated over, `None` once all blocks are used up
+    block_iter: Option<(usize, slice::IterMut<'i, T>)>,
+}
+
+impl<'i, T> Iterator for IterMut<'i, T> {
+    type Item = Option<&'i mut T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.position >= self.len {
+            return None;
+        }
+        let result = loop {
+            match &mut self.block_iter {
+                // in gap before block
+                Some((offset, _)) if self.position < *offset => break None,
+                Some((_, block_iter)) => {
+                    if let Some(next) = block_iter.next() {
+                        break Some(next);
+                    }
+                }
+                // after the last block
+                None => break None,
+            }
+            self.block_iter = self
+                .blocks_iter
+                .next()
+                .map(|(offset, vec)| (*offset, vec.iter_mut()));
+        };
+        self.position += 1;
+        Some(result)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.len.saturating_sub(self.position);
+        (len, Some(len))
+    }
+}
+
+impl<'i, T> ExactSizeIterator for IterMut<'i, T> {}
+
+impl<'i, T> FusedIterator for IterMut<'i, T> {}
+
+pub struct Gaps<'i, T, B = Vec<T>> {
+    /// where the search ends
+    end: usize,
+    /// where the next gap could start
+    position: usize,
+    /// the remaining blocks which could bound a gap
+    blocks_iter: slice::Iter<'i, (usize, B)>,
+    item: PhantomData<&'i T>,
+}
+
+impl<'i, T, B: AsRef<[T]>> Gaps<'i, T, B> {
+    pub(crate) fn new(blocks: &'i [(usize, B)], idxs: Range<usize>) -> Self {
+        let first =
+            blocks.partition_point(|(offset, block)| offset + block.as_ref().len() <= idxs.start);
+        Gaps {
+            end: idxs.end,
+            position: idxs.start,
+            blocks_iter: blocks[first..].iter(),
+            item: PhantomData,
+        }
+    }
+}
+
+impl<'i, T, B: AsRef<[T]>> Iterator for Gaps<'i, T, B> {
+    type Item = Range<usize>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.position < self.end {
+            match self.blocks_iter.next() {
+                Some((_, block)) if block.as_ref().is_empty() => {}
+                Some((offset, block)) => {
+                    let gap = self.position..(*offset).min(self.end);
+                    self.position = self.position.max(offset + block.as_ref().len());
+                    if !gap.is_empty() {
+                        return Some(gap);
+                    }
+                }
+                None => {
+                    let gap = self.position..self.end;
+                    self.position = self.end;
+                    return Some(gap);
+                }
+            }
+        }
+        None
+    }
+}
+
+/// A run of items yielded by `SparseVec::iter_runs`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Run<'i, T> {
+    Loaded(Range<usize>, &'i [T]),
+    Missing(Range<usize>),
+}
+
+pub struct Runs<'i, T, B = Vec<T>> {
+    /// where the iteration ends
+    end: usize,
+    /// where the next run starts
+    position: usize,
+    /// the blocks which may hold items in the remaining range
+    blocks: &'i [(usize, B)],
+    item: PhantomData<&'i T>,
+}
+
+impl<'i, T, B: AsRef<[T]>> Runs<'i, T, B> {
+    pub(crate) fn new(blocks: &'i [(usize, B)], idxs: Range<usize>) -> Self {
+        Runs {
+            blocks: blocks_overlapping(blocks, &idxs),
+            end: idxs.end,
+            position: idxs.start,
+            item: PhantomData,
+        }
+    }
+}
+
+impl<'i, T: 'i, B: AsRef<[T]>> Iterator for Runs<'i, T, B> {
+    type Item = Run<'i, T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.position >= self.end {
+            return None;
+        }
+        // discard blocks that end before the position
+        while let Some(((offset, block), rest)) = self.blocks.split_first() {
+            if offset + block.as_ref().len() > self.position {
+                break;
+            }
+            self.blocks = rest;
+        }
+        let start = self.position;
+        match self.blocks.first() {
+            Some((offset, block)) if *offset <= start => {
+                let block = block.as_ref();
+                self.position = self.end.min(offset + block.len());
+                Some(Run::Loaded(
+                    start..self.position,
+                    &block[(start - offset)..(self.position - offset)],
+                ))
+            }
+            Some((offset, _)) => {
+                self.position = self.end.min(*offset);
+                Some(Run::Missing(start..self.position))
+            }
+            None => {
+                self.position = self.end;
+                Some(Run::Missing(start..self.end))
+            }
+        }
+    }
+}
+
+impl<'i, T: 'i, B: AsRef<[T]>> FusedIterator for Runs<'i, T, B> {}
+
+pub struct LoadedRanges<'i, T, B = Vec<T>> {
+    /// the remaining blocks to be merged into ranges
+    blocks_iter: Peekable<slice::Iter<'i, (usize, B)>>,
+    item: PhantomData<&'i T>,
+}
+
+impl<'i, T, B: AsRef<[T]>> LoadedRanges<'i, T, B> {
+    pub(crate) fn new(blocks: &'i [(usize, B)]) -> Self {
+        LoadedRanges {
+            blocks_iter: blocks.iter().peekable(),
+            item: PhantomData,
+        }
+    }
+}
+
+impl<'i, T, B: AsRef<[T]>> Iterator for LoadedRanges<'i, T, B> {
+    type Item = Range<usize>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (offset, block) = self
+            .blocks_iter
+            .find(|(_, block)| !block.as_ref().is_empty())?;
+        let mut range = *offset..(offset + block.as_ref().len());
+        while let Some((offset, block)) =
+            self.blocks_iter.next_if(|(offset, _)| *offset == range.end)
+        {
+            range.end = offset + block.as_ref().len();
+        }
+        Some(range)
+    }
+}
+
+pub struct IntoIter<T> {
+    /// where the iteration ends
+    len: usize,
+    /// where the next iteration will come from
+    position: usize,
+    /// the remaining blocks to be iterated over
+    blocks_iter: <Blocks<T> as IntoIterator>::IntoIter,
+    /// the current block being iterated over, `None` before the first block and once all blocks are used up
+    block_iter: Option<(usize, vec::IntoIter<T>)>,
+}
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = Option<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.position >= self.len {
+            return None;
+        }
+        let result = loop {
+            match &mut self.block_iter {
+                // in gap before block
+                Some((offset, _)) if self.position < *offset => break None,
+                Some((_, block_iter)) => {
+                    if let Some(next) = block_iter.next() {
+                        break Some(next);
+                    }
+                }
+                None if self.blocks_iter.len() == 0 => break None,
+                None => {}
+            }
+            self.block_iter = self
+                .blocks_iter
+                .next()
+                .map(|(offset, vec)| (offset, vec.into_iter()));
+        };
+        self.position += 1;
+        Some(result)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.len.saturating_sub(self.position);
+        (len, Some(len))
+    }
+}
+
+impl<T> ExactSizeIterator for IntoIter<T> {}
+
+impl<T> FusedIterator for IntoIter<T> {}
+
+#[test]
+fn create_large_empty() {
+    SparseVec::<String>::with_len(usize::MAX);
+}
+
+#[test]
+fn iterate_empty() {
+    assert_eq!(
+        SparseVec::<u32>::with_len(5).iter().collect::<Vec<_>>(),
+        vec![None, None, None, None, None]
+    );
+}
+
+#[test]
+fn iterate_full() {
+    assert_eq!(
+        SparseVec::<u32>::from(vec![1, 2, 3, 4, 5])
+            .iter()
+            .map(|o| o.copied())
+            .collect::<Vec<_>>(),
+        (1..=5).map(Some).collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn gapped_blocks() {
+    let mut vec: SparseVec<u8> = SparseVec::with_len(5);
+    println!("{:?}", &vec);
+    vec.insert_vec(0, vec![1, 2]);
+    println!("{:?}", &vec);
+    vec.insert_vec(3, vec![4, 5]);
+    println!("{:?}", &vec);
+    assert_eq!(
+        vec.iter().map(|o| o.copied()).collect::<Vec<_>>(),
+        vec![Some(1), Some(2), None, Some(4), Some(5)]
+    );
+}
+
+#[test]
+fn following_blocks() {
+    let mut vec: SparseVec<u8> = SparseVec::with_len(5);
+    vec.insert_vec(0, vec![1, 2, 3]);
+    vec.insert_vec(3, vec![4, 5]);
+    assert_eq!(
+        vec.iter().map(|o| o.copied()).collect::<Vec<_>>(),
+        vec![Some(1), Some(2), Some(3), Some(4), Some(5)]
+    );
+}
+
+#[test]
+fn empty_blocks() {
+    let mut vec: SparseVec<u8> = SparseVec::with_len(5);
+    vec.insert_vec(0, vec![]);
+    vec.insert_vec(0, vec![]);
+    assert_eq!(
+        vec.iter().map(|o| o.copied()).collect::<Vec<_>>(),
+        vec![None, None, None, None, None]
+    );
+}
+
+#[test]
+#[should_panic]
+fn overlap_insert_before() {
+    let mut vec: SparseVec<u8> = SparseVec::with_len(5);
+    vec.insert_vec(2, vec![3, 4]);
+    vec.insert_vec(0, vec![1, 2, 3]);
+}
+
+#[test]
+#[should_panic]
+fn overlap_insert_after() {
+    let mut vec: SparseVec<u8> = SparseVec::with_len(5);
+    vec.insert_vec(0, vec![1, 2, 3]);
+    vec.insert_vec(2, vec![3, 4]);
+}
+
+#[test]
+fn iterate_range_empty() {
+    assert_eq!(
+        SparseVec::<u32>::with_len(5)
+            .iter_range(3..5)
+            .collect::<Vec<_>>(),
+        vec![None, None]
+    );
+}
+
+#[test]
+fn iterate_range_full() {
+    assert_eq!(
+        SparseVec::<u32>::from(vec![1, 2, 3, 4, 5])
+            .iter_range(3..5)
+            .map(|o| o.copied())
+            .collect::<Vec<_>>(),
+        (4..=5).map(Some).collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn iterate_empty_full() {
+    assert_eq!(
+        SparseVec::<u32>::with_len(5)
+            .iter_range(0..5)
+            .collect::<Vec<_>>(),
+        vec![None, None, None, None, None]
+    );
+}
+
+#[test]
+fn iterate_range_full_full() {
+    assert_eq!(
+        SparseVec::<u32>::from(vec![1, 2, 3, 4, 5])
+            .iter_range(0..5)
+            .map(|o| o.copied())
+            .collect::<Vec<_>>(),
+        (1..=5).map(Some).collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn iter_range_half_before() {
+    let mut p = SparseVec::<u8>::with_len(20);
+    p.insert_vec(10, (10..20).collect());
+    assert_eq!(
+        p.iter_range(5..20).take(5).collect::<Vec<_>>(),
+        std::iter::repeat_n(None, 5).collect::<Vec<_>>()
+    );
+    assert_eq!(
+        p.iter_range(5..20)
+            .skip(5)
+            .map(|o| o.copied())
+            .collect::<Vec<_>>(),
+        (10..20).map(Some).collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn get_loaded_and_unloaded() {
+    let mut vec: SparseVec<u8> = SparseVec::with_len(10);
+    vec.insert_vec(2, vec![3, 4]);
+    vec.insert_vec(6, vec![7, 8, 9]);
+    assert_eq!(
+        (0..10).map(|i| vec.get(i).copied()).collect::<Vec<_>>(),
+        vec![
+            None,
+            None,
+            Some(3),
+            Some(4),
+            None,
+            None,
+            Some(7),
+            Some(8),
+            Some(9),
+            None
+        ]
+    );
+    assert!(vec.contains(7));
+    assert!(!vec.contains(5));
+    assert!(!vec.contains(100));
+}
+
+#[test]
+fn get_with_empty_blocks() {
+    let mut vec: SparseVec<u8> = SparseVec::with_len(5);
+    vec.insert_vec(1, vec![2, 3]);
+    vec.insert_vec(1, vec![]);
+    vec.insert_vec(3, vec![]);
+    assert_eq!(vec.get(1), Some(&2));
+    assert_eq!(vec.get(2), Some(&3));
+    assert_eq!(vec.get(3), None);
+}
+
+#[test]
+fn get_mut_edits_in_place() {
+    let mut vec: SparseVec<u8> = SparseVec::with_len(5);
+    vec.insert_vec(1, vec![2, 3]);
+    *vec.get_mut(2).unwrap() = 30;
+    assert_eq!(vec.get_mut(0), None);
+    assert_eq!(vec.get(2), Some(&30));
+}
+
+#[test]
+fn iter_range_mut_edits_in_place() {
+    let mut vec: SparseVec<u8> = SparseVec::with_len(10);
+    vec.insert_vec(1, vec![2, 3]);
+    vec.insert_vec(3, vec![4]);
+    vec.insert_vec(3, vec![]);
+    vec.insert_vec(6, vec![7, 8]);
+    let mut visited = vec![];
+    for item in vec.iter_range_mut(2..8) {
+        visited.push(item.is_some());
+        if let Some(item) = item {
+            *item *= 10;
+        }
+    }
+    assert_eq!(visited, vec![true, true, false, false, true, true]);
+    assert_eq!(
+        (0..10).map(|i| vec.get(i).copied()).collect::<Vec<_>>(),
+        vec![
+            None,
+            Some(2),
+            Some(30),
+            Some(40),
+            None,
+            None,
+            Some(70),
+            Some(80),
+            None,
+            None
+        ]
+    );
+}
+
+#[test]
+fn remove_range_splits_blocks() {
+    let mut vec: SparseVec<u8> = SparseVec::with_len(10);
+    vec.insert_vec(0, vec![1, 2, 3, 4]);
+    vec.insert_vec(4, vec![5, 6]);
+    vec.insert_vec(7, vec![8, 9, 10]);
+    vec.remove_range(2..8);
+    assert_eq!(
+        (0..10).map(|i| vec.get(i).copied()).collect::<Vec<_>>(),
+        vec![
+            Some(1),
+            Some(2),
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            Some(9),
+            Some(10)
+        ]
+    );
+    // the gap can be refilled
+    vec.insert_vec(2, vec![3, 4, 5, 6, 7, 8]);
+    assert_eq!(vec.get(7), Some(&8));
+}
+
+#[test]
+fn remove_range_within_block() {
+    let mut vec = SparseVec::from(vec![1, 2, 3, 4, 5]);
+    vec.remove_range(2..3);
+    assert_eq!(
+        (0..5).map(|i| vec.get(i).copied()).collect::<Vec<_>>(),
+        vec![Some(1), Some(2), None, Some(4), Some(5)]
+    );
+    vec.remove_range(0..0);
+    vec.remove_range(3..100);
+    assert_eq!(
+        (0..5).map(|i| vec.get(i).copied()).collect::<Vec<_>>(),
+        vec![Some(1), Some(2), None, None, None]
+    );
+}
+
+#[test]
+fn gaps_between_blocks() {
+    let mut vec: SparseVec<u8> = SparseVec::with_len(12);
+    vec.insert_vec(2, vec![3, 4]);
+    vec.insert_vec(4, vec![5]);
+    vec.insert_vec(7, vec![]);
+    vec.insert_vec(8, vec![9, 10]);
+    assert_eq!(vec.gaps().collect::<Vec<_>>(), vec![0..2, 5..8, 10..12]);
+    assert_eq!(vec.gaps_in(3..9).collect::<Vec<_>>(), vec![5..8]);
+    assert_eq!(vec.gaps_in(9..20).collect::<Vec<_>>(), vec![10..12]);
+    assert_eq!(vec.gaps_in(2..5).count(), 0);
+}
+
+#[test]
+fn gaps_of_empty_and_full() {
+    assert_eq!(
+        SparseVec::<u8>::with_len(5).gaps().collect::<Vec<_>>(),
+        vec![0..5]
+    );
+    assert_eq!(SparseVec::from(vec![1, 2, 3]).gaps().count(), 0);
+    assert_eq!(SparseVec::<u8>::with_len(0).gaps().count(), 0);
+}
+
+#[test]
+fn loaded_ranges_merge_adjacent() {
+    let mut vec: SparseVec<u8> = SparseVec::with_len(12);
+    vec.insert_vec(2, vec![3, 4]);
+    vec.insert_vec(4, vec![5]);
+    vec.insert_vec(5, vec![]);
+    vec.insert_vec(8, vec![9, 10]);
+    vec.insert_vec(10, vec![11]);
+    vec.insert_vec(10, vec![]);
+    assert_eq!(vec.loaded_ranges().collect::<Vec<_>>(), vec![2..5, 8..11]);
+    assert_eq!(SparseVec::<u8>::with_len(5).loaded_ranges().count(), 0);
+}
+
+#[test]
+fn range_loaded_across_blocks() {
+    let mut vec: SparseVec<u8> = SparseVec::with_len(10);
+    vec.insert_vec(2, vec![3, 4]);
+    vec.insert_vec(4, vec![5, 6]);
+    vec.insert_vec(7, vec![8]);
+    assert!(vec.is_range_loaded(2..6));
+    assert!(vec.is_range_loaded(3..5));
+    assert!(vec.is_range_loaded(9..9));
+    assert!(!vec.is_range_loaded(1..3));
+    assert!(!vec.is_range_loaded(5..8));
+    assert!(!SparseVec::from(vec![1, 2]).is_range_loaded(0..3));
+}
+
+#[test]
+fn try_insert_reports_overlap() {
+    let mut vec: SparseVec<u8> = SparseVec::with_len(10);
+    vec.insert_vec(2, vec![3, 4, 5]);
+    assert_eq!(
+        vec.try_insert_vec(0, vec![1, 2, 3]),
+        Err(InsertError::Overlap(2..5))
+    );
+    assert_eq!(
+        vec.try_insert_vec(4, vec![5, 6]),
+        Err(InsertError::Overlap(2..5))
+    );
+    assert_eq!(vec.try_insert_vec(0, vec![1, 2]), Ok(()));
+    assert_eq!(vec.try_insert_vec(5, vec![6]), Ok(()));
+    assert_eq!(vec.loaded_ranges().collect::<Vec<_>>(), vec![0..6]);
+}
+
+#[test]
+fn try_insert_reports_out_of_bounds() {
+    let mut vec: SparseVec<u8> = SparseVec::with_len(5);
+    assert_eq!(
+        vec.try_insert_vec(3, vec![4, 5, 6]),
+        Err(InsertError::OutOfBounds { start: 3, len: 3 })
+    );
+    assert_eq!(
+        vec.try_insert_vec(usize::MAX, vec![1]),
+        Err(InsertError::OutOfBounds {
+            start: usize::MAX,
+            len: 1
+        })
+    );
+    assert_eq!(vec.try_insert_vec(5, vec![]), Ok(()));
+    assert_eq!(vec.loaded_ranges().count(), 0);
+}
+
+#[test]
+fn overwrite_replaces_overlapping_blocks() {
+    let mut vec: SparseVec<u8> = SparseVec::with_len(10);
+    vec.insert_vec(0, vec![1, 2, 3]);
+    vec.insert_vec(4, vec![5]);
+    vec.insert_vec(6, vec![7, 8, 9]);
+    vec.insert_vec_overwrite(2, vec![30, 40, 50, 60, 70]);
+    assert_eq!(
+        (0..10).map(|i| vec.get(i).copied()).collect::<Vec<_>>(),
+        vec![
+            Some(1),
+            Some(2),
+            Some(30),
+            Some(40),
+            Some(50),
+            Some(60),
+            Some(70),
+            Some(8),
+            Some(9),
+            None
+        ]
+    );
+    assert_eq!(vec.loaded_ranges().collect::<Vec<_>>(), vec![0..9]);
+}
+
+#[test]
+#[should_panic]
+fn overwrite_past_end() {
+    let mut vec: SparseVec<u8> = SparseVec::with_len(3);
+    vec.insert_vec_overwrite(2, vec![3, 4]);
+}
+
+#[test]
+fn clear_and_reset() {
+    let mut vec = SparseVec::from(vec![1, 2, 3]);
+    vec.clear();
+    assert_eq!(vec.len(), 3);
+    assert_eq!(vec.gaps().collect::<Vec<_>>(), vec![0..3]);
+    vec.reset(5);
+    assert_eq!(vec.len(), 5);
+    assert_eq!(vec.gaps().collect::<Vec<_>>(), vec![0..5]);
+}
+
+#[test]
+fn set_len_grows_and_truncates() {
+    let mut vec: SparseVec<u8> = SparseVec::with_len(6);
+    vec.insert_vec(1, vec![2, 3, 4]);
+    vec.insert_vec(5, vec![6]);
+    vec.set_len(8);
+    assert_eq!(vec.gaps().collect::<Vec<_>>(), vec![0..1, 4..5, 6..8]);
+    vec.insert_vec(6, vec![7, 8]);
+    vec.set_len(3);
+    assert_eq!(vec.len(), 3);
+    assert_eq!(vec.loaded_ranges().collect::<Vec<_>>(), vec![1..3]);
+    assert_eq!(vec.get(3), None);
+    vec.set_len(5);
+    assert_eq!(vec.gaps().collect::<Vec<_>>(), vec![0..1, 3..5]);
+}
+
+#[test]
+fn splice_insert_shifts_later_blocks() {
+    let mut vec: SparseVec<u8> = SparseVec::with_len(8);
+    vec.insert_vec(0, vec![1, 2, 3]);
+    vec.insert_vec(5, vec![6, 7]);
+    vec.splice_insert(2, vec![20, 21]);
+    assert_eq!(vec.len(), 10);
+    assert_eq!(
+        vec.iter().map(|o| o.copied()).collect::<Vec<_>>(),
+        vec![
+            Some(1),
+            Some(2),
+            Some(20),
+            Some(21),
+            Some(3),
+            None,
+            None,
+            Some(6),
+            Some(7),
+            None
+        ]
+    );
+    vec.splice_insert(10, vec![11]);
+    vec.splice_insert(6, vec![]);
+    assert_eq!(vec.len(), 11);
+    assert_eq!(vec.gaps().collect::<Vec<_>>(), vec![5..7, 9..10]);
+}
+
+#[test]
+fn remove_shifting_moves_later_blocks() {
+    let mut vec: SparseVec<u8> = SparseVec::with_len(10);
+    vec.insert_vec(0, vec![1, 2, 3]);
+    vec.insert_vec(4, vec![5, 6]);
+    vec.insert_vec(8, vec![9]);
+    vec.remove_shifting(2..5);
+    assert_eq!(vec.len(), 7);
+    assert_eq!(
+        vec.iter().map(|o| o.copied()).collect::<Vec<_>>(),
+        vec![Some(1), Some(2), Some(6), None, None, Some(9), None]
+    );
+    vec.remove_shifting(3..3);
+    assert_eq!(vec.len(), 7);
+}
+
+#[test]
+fn iterate_range_rev() {
+    let mut vec: SparseVec<u8> = SparseVec::with_len(10);
+    vec.insert_vec(1, vec![2, 3]);
+    vec.insert_vec(3, vec![4]);
+    vec.insert_vec(6, vec![7, 8]);
+    assert_eq!(
+        vec.iter_range(2..9)
+            .rev()
+            .map(|o| o.copied())
+            .collect::<Vec<_>>(),
+        vec![None, Some(8), Some(7), None, None, Some(4), Some(3)]
+    );
+    assert_eq!(
+        vec.iter().rev().map(|o| o.copied()).collect::<Vec<_>>(),
+        vec.iter()
+            .map(|o| o.copied())
+            .collect::<Vec<_>>()
+            .into_iter()
+            .rev()
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn iterate_from_both_ends() {
+    let mut vec: SparseVec<u8> = SparseVec::with_len(6);
+    vec.insert_vec(0, vec![1, 2]);
+    vec.insert_vec(4, vec![5, 6]);
+    let mut iter = vec.iter();
+    assert_eq!(iter.next(), Some(Some(&1)));
+    assert_eq!(iter.next_back(), Some(Some(&6)));
+    assert_eq!(iter.next_back(), Some(Some(&5)));
+    assert_eq!(iter.next(), Some(Some(&2)));
+    assert_eq!(iter.next_back(), Some(None));
+    assert_eq!(iter.next(), Some(None));
+    assert_eq!(iter.next(), None);
+    assert_eq!(iter.next_back(), None);
+}
+
+#[test]
+fn iter_exact_size() {
+    let mut vec: SparseVec<u8> = SparseVec::with_len(10);
+    vec.insert_vec(2, vec![3, 4]);
+    let mut iter = vec.iter_range(1..6);
+    assert_eq!(iter.len(), 5);
+    iter.next();
+    iter.next_back();
+    assert_eq!(iter.size_hint(), (3, Some(3)));
+    assert_eq!(vec.iter_range(6..6).len(), 0);
+    assert_eq!(vec.iter_range_mut(0..10).len(), 10);
+}
+
+#[test]
+fn iter_present_skips_gaps() {
+    let mut vec: SparseVec<u8> = SparseVec::with_len(usize::MAX);
+    vec.insert_vec(2, vec![3, 4]);
+    vec.insert_vec(1000, vec![1]);
+    assert_eq!(
+        vec.iter_present().collect::<Vec<_>>(),
+        vec![(2, &3), (3, &4), (1000, &1)]
+    );
+    assert_eq!(vec.iter_present().next_back(), Some((1000, &1)));
+}
+
+#[test]
+fn iter_blocks_clipped() {
+    let mut vec: SparseVec<u8> = SparseVec::with_len(10);
+    vec.insert_vec(1, vec![2, 3, 4]);
+    vec.insert_vec(4, vec![5]);
+    vec.insert_vec(7, vec![8, 9]);
+    assert_eq!(
+        vec.iter_blocks().collect::<Vec<_>>(),
+        vec![
+            (1..4, &[2, 3, 4][..]),
+            (4..5, &[5][..]),
+            (7..9, &[8, 9][..])
+        ]
+    );
+    assert_eq!(
+        vec.iter_blocks_in(2..8).collect::<Vec<_>>(),
+        vec![(2..4, &[3, 4][..]), (4..5, &[5][..]), (7..8, &[8][..])]
+    );
+    assert_eq!(vec.iter_blocks_in(5..7).count(), 0);
+}
+
+#[test]
+fn iterate_range_bounds() {
+    let vec = SparseVec::from(vec![1, 2, 3, 4, 5]);
+    let collect = |iter: Iter<'_, u8>| iter.map(|o| o.copied()).collect::<Vec<_>>();
+    assert_eq!(collect(vec.iter_range(..)).len(), 5);
+    assert_eq!(collect(vec.iter_range(3..)), vec![Some(4), Some(5)]);
+    assert_eq!(collect(vec.iter_range(..=1)), vec![Some(1), Some(2)]);
+    assert_eq!(collect(vec.iter_range(4..100)), vec![Some(5)]);
+    assert_eq!(collect(vec.iter_range(10..)), vec![]);
+    assert_eq!(vec.gaps_in(..).count(), 0);
+    assert_eq!(vec.iter_blocks_in(1..=2).count(), 1);
+}
+
+#[test]
+fn index_loaded() {
+    let mut vec: SparseVec<u8> = SparseVec::with_len(10);
+    vec.insert_vec(2, vec![3, 4, 5]);
+    vec.insert_vec(5, vec![6]);
+    assert_eq!(vec[3], 4);
+    assert_eq!(&vec[2..5], &[3, 4, 5]);
+    assert_eq!(&vec[3..4], &[4]);
+    assert_eq!(&vec[8..8], &[] as &[u8]);
+}
+
+#[test]
+#[should_panic]
+fn index_unloaded() {
+    let vec: SparseVec<u8> = SparseVec::with_len(10);
+    let _ = vec[3];
+}
+
+#[test]
+#[should_panic]
+fn index_range_across_blocks() {
+    let mut vec: SparseVec<u8> = SparseVec::with_len(10);
+    vec.insert_vec(2, vec![3, 4, 5]);
+    vec.insert_vec(5, vec![6]);
+    let _ = &vec[4..6];
+}
+
+#[test]
+fn into_iter_by_value() {
+    let mut vec: SparseVec<String> = SparseVec::with_len(5);
+    vec.insert_vec(1, vec!["b".to_owned(), "c".to_owned()]);
+    vec.insert_vec(3, vec!["d".to_owned()]);
+    assert_eq!(
+        vec.into_iter().collect::<Vec<_>>(),
+        vec![
+            None,
+            Some("b".to_owned()),
+            Some("c".to_owned()),
+            Some("d".to_owned()),
+            None
+        ]
+    );
 }
 
-impl<T> SparseVec<T> {
-    pub fn with_len(len: usize) -> Self {
-        SparseVec {
-            len,
-            blocks: vec![],
-        }
-    }
+#[test]
+fn into_blocks_in_order() {
+    let mut vec: SparseVec<u8> = SparseVec::with_len(10);
+    vec.insert_vec(6, vec![7]);
+    vec.insert_vec(1, vec![2, 3]);
+    assert_eq!(vec.into_blocks(), vec![(1, vec![2, 3]), (6, vec![7])]);
+}
 
-    pub fn len(&self) -> usize {
-        self.len
-    }
+#[test]
+fn from_blocks_validates() {
+    let vec = SparseVec::from_blocks(10, vec![(6, vec![7]), (1, vec![2, 3])]).unwrap();
+    assert_eq!(vec.loaded_ranges().collect::<Vec<_>>(), vec![1..3, 6..7]);
+    assert_eq!(
+        SparseVec::from_blocks(10, vec![(1, vec![2, 3]), (2, vec![3])]).unwrap_err(),
+        InsertError::Overlap(1..3)
+    );
+    assert_eq!(
+        SparseVec::from_blocks(2, vec![(1, vec![2, 3])]).unwrap_err(),
+        InsertError::OutOfBounds { start: 1, len: 2 }
+    );
+}
 
-    pub fn iter_range(&self, idxs: Range<usize>) -> Iter<'_, T> {
-        let mut blocks_iter = self.blocks.iter();
-        // discard blocks that come before the start
-        let block_iter = loop {
-            if let Some((offset, vec)) = blocks_iter.next() {
-                if idxs.start < offset + vec.len() {
-                    break Some((
-                        *offset,
-                        vec.iter()
-                            .skip(idxs.start.checked_sub(*offset).unwrap_or(0)),
-                    ));
-                }
-            } else {
-                break None;
-            }
-        };
-        Iter {
-            len: idxs.end,
-            position: idxs.start,
-            blocks_iter,
-            block_iter,
-        }
-    }
+#[test]
+fn collect_from_options() {
+    let vec: SparseVec<u8> = vec![None, Some(2), Some(3), None, Some(5), None]
+        .into_iter()
+        .collect();
+    assert_eq!(vec.len(), 6);
+    assert_eq!(vec.into_blocks(), vec![(1, vec![2, 3]), (4, vec![5])]);
+}
 
-    pub fn iter(&self) -> Iter<'_, T> {
-        Iter {
-            len: self.len,
-            position: 0,
-            blocks_iter: self.blocks.iter(),
-            block_iter: None,
-        }
-    }
+#[test]
+fn memory_usage_counts_blocks() {
+    let mut vec: SparseVec<u32> = SparseVec::with_len(10);
+    assert_eq!(vec.memory_usage(), MemoryUsage::default());
+    vec.insert_vec(0, Vec::with_capacity(4));
+    vec.insert_vec(1, vec![2, 3]);
+    vec.insert_vec(6, vec![7]);
+    let usage = vec.memory_usage();
+    assert_eq!(usage.items, 3);
+    assert_eq!(usage.blocks, 2);
+    assert!(usage.heap_bytes >= 3 * 4);
+    // with the smallvec feature, the block list is inline
+    #[cfg(not(feature = "smallvec"))]
+    assert!(usage.heap_bytes >= 3 * 4 + 2 * mem::size_of::<(usize, Vec<u32>)>());
+}
 
-    /// Insert data into empty space
-    // Panics if space is occupied
-    pub fn insert_vec(&mut self, start: usize, vec: Vec<T>) {
-        let insert_pos = self
-            .blocks
-            .iter()
-            .position(|(offset, _)| *offset >= start)
-            .unwrap_or(self.blocks.len());
-        assert!(
-            insert_pos == 0
-                || start >= (self.blocks[insert_pos - 1].0 + self.blocks[insert_pos - 1].1.len()),
-            "Inserted vec overlaps existing block"
-        );
-        assert!(
-            start + vec.len()
-                <= self
-                    .blocks
-                    .get(insert_pos)
-                    .map(|(offset, _)| *offset)
-                    .unwrap_or(usize::MAX),
-            "Inserted vec overlaps existing block"
-        );
-        self.blocks.insert(insert_pos, (start, vec));
-    }
+#[test]
+fn evict_outside_trims() {
+    let mut vec: SparseVec<u8> = SparseVec::with_len(10);
+    vec.insert_vec(0, vec![1, 2, 3]);
+    vec.insert_vec(4, vec![5, 6]);
+    vec.insert_vec(7, vec![8, 9, 10]);
+    vec.evict_outside(2..8);
+    assert_eq!(
+        vec.loaded_ranges().collect::<Vec<_>>(),
+        vec![2..3, 4..6, 7..8]
+    );
+    vec.evict_outside(5..5);
+    assert_eq!(vec.loaded_ranges().count(), 0);
 }
 
-impl<T> From<Vec<T>> for SparseVec<T> {
-    fn from(vec: Vec<T>) -> Self {
-        Self {
-            len: vec.len(),
-            blocks: vec![(0, vec)],
-        }
-    }
+#[test]
+fn evict_outside_several_ranges() {
+    let mut vec: SparseVec<u8> = SparseVec::with_len(20);
+    vec.insert_vec(0, vec![0; 20]);
+    vec.evict_outside_ranges(&[12..15, 0..2, 13..17]);
+    assert_eq!(vec.loaded_ranges().collect::<Vec<_>>(), vec![0..2, 12..17]);
+    vec.evict_outside_ranges(&[]);
+    assert_eq!(vec.loaded_ranges().count(), 0);
 }
 
-pub struct Iter<'i, T> {
-    /// where the iteration ends
-    len: usize,
-    /// where the next iteration will come from
-    position: usize,
-    /// the remaining blocks to be iterated over
-    blocks_iter: slice::Iter<'i, (usize, Vec<T>)>,
-    /// the current block being iteratred over
-    block_iter: Option<(usize, Skip<slice::Iter<'i, T>>)>,
+#[test]
+fn drain_range_takes_items() {
+    let mut vec: SparseVec<String> = SparseVec::with_len(6);
+    vec.insert_vec(0, vec!["a".to_owned(), "b".to_owned()]);
+    vec.insert_vec(3, vec!["d".to_owned(), "e".to_owned()]);
+    assert_eq!(
+        vec.drain_range(1..4),
+        vec![Some("b".to_owned()), None, Some("d".to_owned())]
+    );
+    assert_eq!(vec.loaded_ranges().collect::<Vec<_>>(), vec![0..1, 4..5]);
+    assert_eq!(vec.drain_range(5..), vec![None]);
 }
 
-impl<'i, T> Iter<'i, T> {
-    fn next_block(&mut self) {
-        self.block_iter = self
-            .blocks_iter
-            .next()
-            .map(|(offset, vec)| (*offset, vec.iter().skip(0)));
-    }
+#[test]
+fn contiguous_slice_only_within_block() {
+    let mut vec: SparseVec<u8> = SparseVec::with_len(10);
+    vec.insert_vec(2, vec![3, 4, 5]);
+    vec.insert_vec(5, vec![6]);
+    assert_eq!(vec.as_contiguous_slice(3..5), Some(&[4, 5][..]));
+    assert_eq!(vec.as_contiguous_slice(5..6), Some(&[6][..]));
+    assert_eq!(vec.as_contiguous_slice(4..6), None);
+    assert_eq!(vec.as_contiguous_slice(0..2), None);
+    assert_eq!(vec.as_contiguous_slice(2..6), None);
 }
 
-impl<'i, T> Iterator for Iter<'i, T> {
-    type Item = Option<&'i T>;
+#[test]
+fn first_gap_after_index() {
+    let mut vec: SparseVec<u8> = SparseVec::with_len(10);
+    vec.insert_vec(2, vec![3, 4]);
+    vec.insert_vec(4, vec![5]);
+    vec.insert_vec(8, vec![9, 10]);
+    assert_eq!(vec.first_gap_at_or_after(0), Some(0..2));
+    assert_eq!(vec.first_gap_at_or_after(1), Some(1..2));
+    assert_eq!(vec.first_gap_at_or_after(2), Some(5..8));
+    assert_eq!(vec.first_gap_at_or_after(8), None);
+    assert_eq!(vec.first_gap_at_or_after(20), None);
+}
 
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.position >= self.len {
-            return None;
-        }
-        if self.block_iter.is_none() {
-            self.next_block();
-        }
-        let result = if let Some((offset, block_iter)) = &mut self.block_iter {
-            if self.position < *offset {
-                // in gap before block
-                Some(None)
-            } else if let Some(next) = block_iter.next() {
-                Some(Some(next))
-            } else {
-                // after block
-                self.next_block();
-                if let Some((offset, block_iter)) = &mut self.block_iter {
-                    if self.position < *offset {
-                        // in gap before block
-                        Some(None)
-                    } else if let Some(next) = block_iter.next() {
-                        Some(Some(next))
-                    } else {
-                        // after block
-                        Some(None)
-                    }
-                } else {
-                    // iter is empty
-                    Some(None)
-                }
-            }
+#[test]
+fn defragment_merges_adjacent() {
+    let mut vec: SparseVec<u8> = SparseVec::with_len(10);
+    vec.insert_vec(0, vec![1]);
+    vec.insert_vec(1, vec![2, 3]);
+    vec.insert_vec(3, vec![4]);
+    vec.insert_vec(6, vec![7]);
+    vec.insert_vec(7, vec![8]);
+    vec.defragment();
+    assert_eq!(
+        vec.into_blocks(),
+        vec![(0, vec![1, 2, 3, 4]), (6, vec![7, 8])]
+    );
+}
+
+#[test]
+fn retain_leaves_gaps() {
+    let mut vec: SparseVec<u8> = SparseVec::with_len(10);
+    vec.insert_vec(0, vec![1, 2, 3, 4, 5]);
+    vec.insert_vec(7, vec![8, 9]);
+    vec.retain(|idx, item| idx != 1 && *item != 4 && *item != 9);
+    assert_eq!(
+        vec.into_blocks(),
+        vec![(0, vec![1]), (2, vec![3]), (4, vec![5]), (7, vec![8])]
+    );
+}
+
+#[test]
+fn map_keeps_positions() {
+    let mut vec: SparseVec<u8> = SparseVec::with_len(5);
+    vec.insert_vec(1, vec![2, 3]);
+    vec.insert_vec(4, vec![5]);
+    let strings = vec.map_ref(|item| item.to_string());
+    assert_eq!(
+        strings.iter().map(|o| o.cloned()).collect::<Vec<_>>(),
+        vec![
+            None,
+            Some("2".to_owned()),
+            Some("3".to_owned()),
+            None,
+            Some("5".to_owned())
+        ]
+    );
+    let doubled = vec.map(|item| u32::from(item) * 2);
+    assert_eq!(doubled.len(), 5);
+    assert_eq!(doubled.into_blocks(), vec![(1, vec![4, 6]), (4, vec![10])]);
+}
+
+#[test]
+fn equality_ignores_block_split() {
+    let mut split: SparseVec<u8> = SparseVec::with_len(6);
+    split.insert_vec(1, vec![2]);
+    split.insert_vec(2, vec![3, 4]);
+    let mut whole: SparseVec<u8> = SparseVec::with_len(6);
+    whole.insert_vec(1, vec![2, 3, 4]);
+    assert_eq!(split, whole);
+    whole.insert_vec(5, vec![6]);
+    assert_ne!(split, whole);
+    split.insert_vec(5, vec![7]);
+    assert_ne!(split, whole);
+    assert_ne!(SparseVec::<u8>::with_len(1), SparseVec::<u8>::with_len(2));
+}
+
+#[test]
+fn unknown_len_grows_with_inserts() {
+    let mut vec: SparseVec<u8> = SparseVec::with_unknown_len();
+    assert_eq!(vec.known_len(), None);
+    assert_eq!(vec.len(), 0);
+    vec.insert_vec(10, vec![11, 12]);
+    vec.insert_vec(2, vec![3]);
+    assert_eq!(vec.len(), 12);
+    assert_eq!(vec.known_len(), None);
+    assert_eq!(vec.gaps().collect::<Vec<_>>(), vec![0..2, 3..10]);
+    assert_ne!(
+        vec,
+        SparseVec::from_blocks(12, vec![(10, vec![11, 12]), (2, vec![3])]).unwrap()
+    );
+    vec.set_len(11);
+    assert_eq!(vec.known_len(), Some(11));
+    assert!(vec.try_insert_vec(11, vec![12]).is_err());
+}
+
+#[test]
+fn fill_gaps_with_value() {
+    let mut vec: SparseVec<u8> = SparseVec::with_len(10);
+    vec.insert_vec(2, vec![3, 4]);
+    vec.insert_vec(6, vec![7]);
+    vec.fill(1..8, 0);
+    assert_eq!(
+        vec.iter().map(|o| o.copied()).collect::<Vec<_>>(),
+        vec![
+            None,
+            Some(0),
+            Some(3),
+            Some(4),
+            Some(0),
+            Some(0),
+            Some(7),
+            Some(0),
+            None,
+            None
+        ]
+    );
+    let mut unknown: SparseVec<u8> = SparseVec::with_unknown_len();
+    unknown.fill(2..4, 1);
+    assert_eq!(unknown.len(), 4);
+    assert_eq!(unknown.loaded_ranges().collect::<Vec<_>>(), vec![2..4]);
+}
+
+#[test]
+#[should_panic]
+fn fill_past_end() {
+    let mut vec: SparseVec<u8> = SparseVec::with_len(3);
+    vec.fill(2..4, 0);
+}
+
+#[test]
+fn set_overwrites_or_joins_neighbours() {
+    let mut vec: SparseVec<u8> = SparseVec::with_len(10);
+    vec.insert_vec(2, vec![3, 4]);
+    vec.insert_vec(6, vec![7]);
+    assert_eq!(vec.set(3, 40), Some(4));
+    assert_eq!(vec.set(4, 5), None);
+    assert_eq!(vec.set(5, 6), None);
+    assert_eq!(vec.set(0, 1), None);
+    assert_eq!(vec.set(9, 10), None);
+    assert_eq!(vec.iter_blocks().count(), 4);
+    assert_eq!(
+        vec.iter().map(|o| o.copied()).collect::<Vec<_>>(),
+        vec![
+            Some(1),
+            None,
+            Some(3),
+            Some(40),
+            Some(5),
+            Some(6),
+            Some(7),
+            None,
+            None,
+            Some(10)
+        ]
+    );
+    let mut unknown: SparseVec<u8> = SparseVec::with_unknown_len();
+    unknown.set(4, 5);
+    assert_eq!(unknown.len(), 5);
+}
+
+#[test]
+#[should_panic]
+fn set_past_end() {
+    let mut vec: SparseVec<u8> = SparseVec::with_len(3);
+    vec.set(3, 0);
+}
+
+#[test]
+fn merge_with_policy() {
+    let mut ours: SparseVec<u8> = SparseVec::with_len(10);
+    ours.insert_vec(2, vec![1, 1]);
+    ours.insert_vec(7, vec![1]);
+    let mut theirs: SparseVec<u8> = SparseVec::with_len(10);
+    theirs.insert_vec(0, vec![2; 5]);
+    theirs.insert_vec(6, vec![2; 2]);
+
+    let mut merged = ours.clone();
+    merged
+        .merge(theirs.clone(), MergePolicy::PreferSelf)
+        .unwrap();
+    assert_eq!(
+        merged.iter().map(|o| o.copied()).collect::<Vec<_>>(),
+        vec![
+            Some(2),
+            Some(2),
+            Some(1),
+            Some(1),
+            Some(2),
+            None,
+            Some(2),
+            Some(1),
+            None,
+            None
+        ]
+    );
+
+    let mut merged = ours.clone();
+    merged
+        .merge(theirs.clone(), MergePolicy::PreferOther)
+        .unwrap();
+    assert_eq!(
+        merged.iter().map(|o| o.copied()).collect::<Vec<_>>(),
+        vec![
+            Some(2),
+            Some(2),
+            Some(2),
+            Some(2),
+            Some(2),
+            None,
+            Some(2),
+            Some(2),
+            None,
+            None
+        ]
+    );
+
+    let mut merged = ours.clone();
+    assert_eq!(
+        merged.merge(theirs, MergePolicy::Error),
+        Err(InsertError::Overlap(2..4))
+    );
+    assert_eq!(merged, ours);
+}
+
+#[test]
+fn merge_past_end_of_unknown_len() {
+    for policy in [MergePolicy::PreferSelf, MergePolicy::PreferOther] {
+        let mut ours: SparseVec<u8> = SparseVec::with_unknown_len();
+        ours.insert_vec(0, vec![1, 1]);
+        let mut theirs: SparseVec<u8> = SparseVec::with_unknown_len();
+        theirs.insert_vec(0, vec![2; 5]);
+        ours.merge(theirs, policy).unwrap();
+        assert_eq!(ours.len(), 5, "{:?}", policy);
+        assert!(ours.is_range_loaded(0..5), "{:?}", policy);
+        assert_eq!(ours.get(4), Some(&2));
+        let first = if policy == MergePolicy::PreferSelf {
+            1
         } else {
-            // iter is empty
-            Some(None)
+            2
         };
-        self.position += 1;
-        result
+        assert_eq!(ours.get(0), Some(&first));
     }
 }
 
 #[test]
-fn create_large_empty() {
-    SparseVec::<String>::with_len(usize::MAX);
+fn merge_out_of_bounds_is_unchanged() {
+    let mut ours: SparseVec<u8> = SparseVec::with_len(4);
+    ours.insert_vec(0, vec![1]);
+    let mut theirs: SparseVec<u8> = SparseVec::with_len(10);
+    theirs.insert_vec(1, vec![2]);
+    theirs.insert_vec(3, vec![2, 2]);
+    let mut merged = ours.clone();
+    assert_eq!(
+        merged.merge(theirs, MergePolicy::PreferOther),
+        Err(InsertError::OutOfBounds { start: 3, len: 2 })
+    );
+    assert_eq!(merged, ours);
 }
 
 #[test]
-fn iterate_empty() {
+fn stats_summarise_coverage() {
+    let mut vec: SparseVec<u8> = SparseVec::with_len(10);
     assert_eq!(
-        SparseVec::<u32>::with_len(5).iter().collect::<Vec<_>>(),
-        vec![None, None, None, None, None]
+        vec.stats(),
+        Stats {
+            loaded_items: 0,
+            blocks: 0,
+            gaps: 1,
+            largest_gap: 10,
+            coverage: 0.
+        }
     );
+    vec.insert_vec(1, vec![0; 2]);
+    vec.insert_vec(3, vec![0]);
+    vec.insert_vec(8, vec![0]);
+    assert_eq!(
+        vec.stats(),
+        Stats {
+            loaded_items: 4,
+            blocks: 3,
+            gaps: 3,
+            largest_gap: 4,
+            coverage: 0.4
+        }
+    );
+    assert_eq!(SparseVec::<u8>::with_len(0).stats().coverage, 1.);
 }
 
 #[test]
-fn iterate_full() {
+fn iter_range_rev_descends() {
+    let mut vec: SparseVec<u8> = SparseVec::with_len(6);
+    vec.insert_vec(0, vec![1]);
+    vec.insert_vec(3, vec![4, 5]);
     assert_eq!(
-        SparseVec::<u32>::from(vec![1, 2, 3, 4, 5])
-            .iter()
+        vec.iter_range_rev(..5)
             .map(|o| o.copied())
             .collect::<Vec<_>>(),
-        (1..=5).map(Some).collect::<Vec<_>>()
+        vec![Some(5), Some(4), None, None, Some(1)]
     );
+    assert_eq!(vec.iter_range_rev(4..).len(), 2);
 }
 
+#[cfg(feature = "rayon")]
 #[test]
-fn gapped_blocks() {
-    let mut vec: SparseVec<u8> = SparseVec::with_len(5);
-    println!("{:?}", &vec);
-    vec.insert_vec(0, vec![1, 2]);
-    println!("{:?}", &vec);
-    vec.insert_vec(3, vec![4, 5]);
-    println!("{:?}", &vec);
+fn par_iter_blocks_matches_iter_blocks() {
+    use rayon::prelude::*;
+    let mut vec = SparseVec::with_len(100);
+    vec.insert_vec(3, (3..10).collect());
+    vec.insert_vec(40, (40..60).collect());
     assert_eq!(
-        vec.iter().map(|o| o.copied()).collect::<Vec<_>>(),
-        vec![Some(1), Some(2), None, Some(4), Some(5)]
+        vec.par_iter_blocks().collect::<Vec<_>>(),
+        vec.iter_blocks().collect::<Vec<_>>()
+    );
+    assert_eq!(
+        vec.par_iter_blocks()
+            .map(|(_, block)| block.iter().sum::<usize>())
+            .sum::<usize>(),
+        vec.iter_present().map(|(_, item)| item).sum::<usize>()
     );
 }
 
 #[test]
-fn following_blocks() {
-    let mut vec: SparseVec<u8> = SparseVec::with_len(5);
-    vec.insert_vec(0, vec![1, 2, 3]);
-    vec.insert_vec(3, vec![4, 5]);
+fn first_and_last_loaded() {
+    let mut vec: SparseVec<u8> = SparseVec::with_len(10);
+    assert_eq!((vec.first_loaded(), vec.last_loaded()), (None, None));
+    vec.insert_vec(6, vec![7, 8]);
+    vec.insert_vec(2, vec![3]);
+    assert_eq!((vec.first_loaded(), vec.last_loaded()), (Some(2), Some(7)));
+    assert_eq!(SparseVec::<u8>::from(vec![]).first_loaded(), None);
+}
+
+#[test]
+fn nearest_loaded_either_side() {
+    let mut vec: SparseVec<u8> = SparseVec::with_len(20);
+    assert_eq!(vec.nearest_loaded(5), None);
+    vec.insert_vec(4, vec![0, 0]);
+    vec.insert_vec(12, vec![0]);
+    assert_eq!(vec.nearest_loaded(0), Some(4));
+    assert_eq!(vec.nearest_loaded(5), Some(5));
+    assert_eq!(vec.nearest_loaded(8), Some(5));
+    assert_eq!(vec.nearest_loaded(9), Some(12));
+    assert_eq!(vec.nearest_loaded(19), Some(12));
+}
+
+#[test]
+fn get_many_in_given_order() {
+    let mut vec: SparseVec<u8> = SparseVec::with_len(20);
+    vec.insert_vec(2, vec![3, 4]);
+    vec.insert_vec(10, vec![11]);
     assert_eq!(
-        vec.iter().map(|o| o.copied()).collect::<Vec<_>>(),
-        vec![Some(1), Some(2), Some(3), Some(4), Some(5)]
+        vec.get_many(&[10, 3, 0, 3, 19, 2]),
+        vec![Some(&11), Some(&4), None, Some(&4), None, Some(&3)]
     );
+    assert!(vec.get_many(&[]).is_empty());
 }
 
 #[test]
-fn empty_blocks() {
-    let mut vec: SparseVec<u8> = SparseVec::with_len(5);
-    vec.insert_vec(0, vec![]);
-    vec.insert_vec(0, vec![]);
+fn shrink_to_fit_reclaims_capacity() {
+    let mut vec: SparseVec<u64> = SparseVec::with_len(100);
+    let mut block = Vec::with_capacity(50);
+    block.extend(0..10);
+    vec.insert_vec(0, block);
+    let before = vec.memory_usage().heap_bytes;
+    let reclaimed = vec.shrink_to_fit();
+    assert!(reclaimed >= 40 * 8);
+    assert_eq!(vec.memory_usage().heap_bytes, before - reclaimed);
+    assert_eq!(vec.shrink_to_fit(), 0);
+}
+
+#[test]
+fn copy_range_into_buffer() {
+    let mut vec: SparseVec<u8> = SparseVec::with_len(10);
+    vec.insert_vec(0, vec![1, 2]);
+    vec.insert_vec(2, vec![3, 4]);
+    vec.insert_vec(7, vec![8]);
+    let mut out = vec![0];
+    assert_eq!(vec.copy_range_into(1..4, &mut out), Ok(()));
+    assert_eq!(out, vec![0, 2, 3, 4]);
+    assert_eq!(vec.copy_range_into(3..8, &mut out), Err(GapError(4..7)));
+    assert_eq!(vec.copy_range_into(7..11, &mut out), Err(GapError(10..11)));
+    assert_eq!(out, vec![0, 2, 3, 4]);
+    assert_eq!(vec.extend_from_range(3.., &mut out), 2);
+    assert_eq!(out, vec![0, 2, 3, 4, 4, 8]);
+}
+
+#[test]
+fn split_at_straddling_block() {
+    let mut vec: SparseVec<u8> = SparseVec::with_len(10);
+    vec.insert_vec(1, vec![2]);
+    vec.insert_vec(3, vec![4, 5, 6]);
+    vec.insert_vec(8, vec![9]);
+    let (before, after) = vec.split_at(4);
+    assert_eq!(before.len(), 4);
     assert_eq!(
-        vec.iter().map(|o| o.copied()).collect::<Vec<_>>(),
-        vec![None, None, None, None, None]
+        before.iter().map(|o| o.copied()).collect::<Vec<_>>(),
+        vec![None, Some(2), None, Some(4)]
+    );
+    assert_eq!(after.len(), 6);
+    assert_eq!(
+        after.iter().map(|o| o.copied()).collect::<Vec<_>>(),
+        vec![Some(5), Some(6), None, None, Some(9), None]
     );
+    let (empty, all) = before.clone().split_at(0);
+    assert_eq!((empty.len(), all), (0, before));
 }
 
 #[test]
-#[should_panic]
-fn overlap_insert_before() {
-    let mut vec: SparseVec<u8> = SparseVec::with_len(5);
-    vec.insert_vec(2, vec![3, 4]);
-    vec.insert_vec(0, vec![1, 2, 3]);
+fn append_shifts_other() {
+    let mut vec: SparseVec<u8> = SparseVec::with_len(3);
+    vec.insert_vec(1, vec![2, 3]);
+    let mut other = SparseVec::with_len(3);
+    other.insert_vec(0, vec![4]);
+    other.insert_vec(2, vec![6]);
+    vec.append(other);
+    assert_eq!(vec.known_len(), Some(6));
+    assert_eq!(
+        vec.iter().map(|o| o.copied()).collect::<Vec<_>>(),
+        vec![None, Some(2), Some(3), Some(4), None, Some(6)]
+    );
+    vec.append(SparseVec::with_unknown_len());
+    assert_eq!(vec.known_len(), None);
 }
 
 #[test]
-#[should_panic]
-fn overlap_insert_after() {
-    let mut vec: SparseVec<u8> = SparseVec::with_len(5);
-    vec.insert_vec(0, vec![1, 2, 3]);
-    vec.insert_vec(2, vec![3, 4]);
+fn coverage_set_operations() {
+    let mut session: SparseVec<u8> = SparseVec::with_len(30);
+    session.insert_vec(0, vec![0; 5]);
+    session.insert_vec(10, vec![0; 10]);
+    let mut disk: SparseVec<u16> = SparseVec::with_len(30);
+    disk.insert_vec(3, vec![0; 4]);
+    disk.insert_vec(7, vec![0; 5]);
+    disk.insert_vec(15, vec![0; 2]);
+    disk.insert_vec(19, vec![0; 11]);
+    assert_eq!(
+        session.loaded_intersection(&disk),
+        vec![3..5, 10..12, 15..17, 19..20]
+    );
+    assert_eq!(
+        disk.loaded_intersection(&session),
+        session.loaded_intersection(&disk)
+    );
+    assert_eq!(session.missing_relative_to(&disk), vec![5..10, 20..30]);
+    assert_eq!(
+        disk.missing_relative_to(&session),
+        vec![0..3, 12..15, 17..19]
+    );
 }
 
 #[test]
-fn iterate_range_empty() {
+fn coverage_string_scales() {
+    let mut vec: SparseVec<u8> = SparseVec::with_len(20);
+    vec.insert_vec(0, vec![0; 8]);
+    vec.insert_vec(13, vec![0; 2]);
+    assert_eq!(vec.coverage_string(10), "████░░▒▒░░");
+    assert_eq!(vec.coverage_string(100).chars().count(), 20);
+    assert_eq!(SparseVec::<u8>::with_len(0).coverage_string(10), "");
     assert_eq!(
-        SparseVec::<u32>::with_len(5)
-            .iter_range(3..5)
-            .collect::<Vec<_>>(),
-        vec![None, None]
+        SparseVec::<u8>::with_len(usize::MAX).coverage_string(3),
+        "░░░"
+    );
+    assert_eq!(
+        format!("{:?}", vec),
+        "SparseVec { len: 20, len_known: true, coverage: \"████████░░░░░██░░░░░\", blocks: {0: [0, 0, 0, 0, 0, 0, 0, 0], 13: [0, 0]} }"
     );
 }
 
 #[test]
-fn iterate_range_full() {
+fn iter_range_starting_deep_in_fragmented_vec() {
+    let mut vec: SparseVec<usize> = SparseVec::with_len(2000);
+    for start in (0..2000).step_by(2) {
+        vec.insert_vec(start, vec![start]);
+    }
     assert_eq!(
-        SparseVec::<u32>::from(vec![1, 2, 3, 4, 5])
-            .iter_range(3..5)
+        vec.iter_range(1996..)
             .map(|o| o.copied())
             .collect::<Vec<_>>(),
-        (4..=5).map(Some).collect::<Vec<_>>()
+        vec![Some(1996), None, Some(1998), None]
     );
+    for item in vec.iter_range_mut(1997..1999).flatten() {
+        *item += 1;
+    }
+    assert_eq!(vec.get(1998), Some(&1999));
+    assert_eq!(vec.get(1996), Some(&1996));
 }
 
 #[test]
-fn iterate_empty_full() {
+fn iter_runs_alternate() {
+    let mut vec: SparseVec<u8> = SparseVec::with_len(10);
+    vec.insert_vec(1, vec![2, 3]);
+    vec.insert_vec(3, vec![4]);
+    vec.insert_vec(6, vec![7, 8]);
     assert_eq!(
-        SparseVec::<u32>::with_len(5)
-            .iter_range(0..5)
-            .collect::<Vec<_>>(),
-        vec![None, None, None, None, None]
+        vec.iter_runs(..).collect::<Vec<_>>(),
+        vec![
+            Run::Missing(0..1),
+            Run::Loaded(1..3, &[2, 3][..]),
+            Run::Loaded(3..4, &[4][..]),
+            Run::Missing(4..6),
+            Run::Loaded(6..8, &[7, 8][..]),
+            Run::Missing(8..10),
+        ]
+    );
+    assert_eq!(
+        vec.iter_runs(2..7).collect::<Vec<_>>(),
+        vec![
+            Run::Loaded(2..3, &[3][..]),
+            Run::Loaded(3..4, &[4][..]),
+            Run::Missing(4..6),
+            Run::Loaded(6..7, &[7][..]),
+        ]
     );
+    assert_eq!(vec.iter_runs(5..5).next(), None);
 }
 
 #[test]
-fn iterate_range_full_full() {
+fn iterate_long_chain_of_adjacent_blocks() {
+    let mut vec: SparseVec<usize> = SparseVec::with_len(12);
+    for idx in (1..11).rev() {
+        vec.insert_vec(idx, vec![idx]);
+    }
+    let expected: Vec<_> = (0..12)
+        .map(|idx| Some(idx).filter(|idx| (1..11).contains(idx)))
+        .collect();
+    assert_eq!(vec.iter().map(|o| o.copied()).collect::<Vec<_>>(), expected);
     assert_eq!(
-        SparseVec::<u32>::from(vec![1, 2, 3, 4, 5])
-            .iter_range(0..5)
+        vec.iter().rev().map(|o| o.copied()).collect::<Vec<_>>(),
+        expected.iter().rev().copied().collect::<Vec<_>>()
+    );
+    assert_eq!(
+        vec.iter_range_mut(..)
             .map(|o| o.copied())
             .collect::<Vec<_>>(),
-        (1..=5).map(Some).collect::<Vec<_>>()
+        expected
     );
+    assert_eq!(vec.into_iter().collect::<Vec<_>>(), expected);
 }
 
 #[test]
-fn iter_range_half_before() {
-    let mut p = SparseVec::<u8>::with_len(20);
-    p.insert_vec(10, (10..20).collect());
+fn iterate_after_growing_from_empty_vec() {
+    let mut vec: SparseVec<u8> = SparseVec::from(vec![]);
+    vec.set_len(4);
+    vec.insert_vec(0, vec![1]);
+    vec.insert_vec(1, vec![2]);
+    vec.insert_vec(3, vec![4]);
+    assert_eq!(vec.iter_blocks().count(), 3);
+    let expected = vec![Some(1), Some(2), None, Some(4)];
+    assert_eq!(vec.iter().map(|o| o.copied()).collect::<Vec<_>>(), expected);
     assert_eq!(
-        p.iter_range(5..20).take(5).collect::<Vec<_>>(),
-        std::iter::repeat(None).take(5).collect::<Vec<_>>()
+        vec.iter().rev().map(|o| o.copied()).collect::<Vec<_>>(),
+        expected.iter().rev().copied().collect::<Vec<_>>()
     );
     assert_eq!(
-        p.iter_range(5..20)
-            .skip(5)
+        vec.iter_range_mut(..)
             .map(|o| o.copied())
             .collect::<Vec<_>>(),
-        (10..20).map(|s| Some(s)).collect::<Vec<_>>()
+        expected
     );
+    assert_eq!(vec.gaps().collect::<Vec<_>>(), vec![2..3]);
+    assert_eq!(vec.loaded_ranges().collect::<Vec<_>>(), vec![0..2, 3..4]);
+    assert_eq!(vec.into_iter().collect::<Vec<_>>(), expected);
+}
+
+#[cfg(feature = "smallvec")]
+#[test]
+fn few_blocks_held_inline() {
+    let mut vec: SparseVec<u32> = SparseVec::with_len(10);
+    vec.insert_vec(1, vec![2, 3]);
+    vec.insert_vec(6, vec![7]);
+    assert!(!vec.blocks.spilled());
+    assert_eq!(vec.memory_usage().heap_bytes, 3 * 4);
+    for idx in [0, 3, 8] {
+        vec.insert_vec(idx, vec![0]);
+    }
+    assert!(vec.blocks.spilled());
 }
@@ -0,0 +1,143 @@
+use super::SparseVec;
+
+/// A position in a SparseVec which remembers the block it is in, so that moving to nearby indices doesn't search the blocks again
+#[derive(Debug, Clone)]
+pub struct Cursor<'i, T> {
+    vec: &'i SparseVec<T>,
+    /// the position, from 0 to the length inclusive
+    position: usize,
+    /// the first block which ends after the position
+    block: usize,
+}
+
+impl<'i, T> Cursor<'i, T> {
+    pub(super) fn new(vec: &'i SparseVec<T>, idx: usize) -> Self {
+        let mut cursor = Cursor {
+            vec,
+            position: 0,
+            block: 0,
+        };
+        cursor.seek(idx);
+        cursor
+    }
+
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// The item at the position if it is loaded
+    pub fn get(&self) -> Option<&'i T> {
+        let (offset, block) = self.vec.blocks.get(self.block)?;
+        block.get(self.position.checked_sub(*offset)?)
+    }
+
+    /// Move to `idx`, clamped to the length, this is O(1) if it is in the current block or one next to it
+    pub fn seek(&mut self, idx: usize) {
+        self.position = idx.min(self.vec.len);
+        let blocks = &self.vec.blocks;
+        let is_block = |pos: usize| {
+            (pos == blocks.len() || {
+                let (offset, block) = &blocks[pos];
+                offset + block.len() > self.position
+            }) && (pos == 0 || {
+                let (offset, block) = &blocks[pos - 1];
+                offset + block.len() <= self.position
+            })
+        };
+        self.block = [
+            Some(self.block),
+            self.block.checked_add(1),
+            self.block.checked_sub(1),
+        ]
+        .iter()
+        .flatten()
+        .copied()
+        .find(|pos| *pos <= blocks.len() && is_block(*pos))
+        .unwrap_or_else(|| {
+            blocks.partition_point(|(offset, block)| offset + block.len() <= self.position)
+        });
+    }
+
+    /// Move back one index and get the item there if it is loaded, or `None` if already at the start
+    pub fn prev(&mut self) -> Option<Option<&'i T>> {
+        let idx = self.position.checked_sub(1)?;
+        self.seek(idx);
+        Some(self.get())
+    }
+
+    /// Move to the next loaded index at or after the position and return it, or move to the end if there is none
+    pub fn skip_to_next_loaded(&mut self) -> Option<usize> {
+        match self.vec.blocks.get(self.block) {
+            Some((offset, _)) => {
+                self.position = self.position.max(*offset);
+                Some(self.position)
+            }
+            None => {
+                self.position = self.vec.len;
+                None
+            }
+        }
+    }
+}
+
+/// Get the item at the position if it is loaded and move forward one index, or `None` if at the end
+impl<'i, T> Iterator for Cursor<'i, T> {
+    type Item = Option<&'i T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.position >= self.vec.len {
+            return None;
+        }
+        let item = self.get();
+        self.seek(self.position + 1);
+        Some(item)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.vec.len - self.position;
+        (len, Some(len))
+    }
+}
+
+#[test]
+fn walk_forwards_and_back() {
+    let mut vec = SparseVec::with_len(8);
+    vec.insert_vec(1, vec![2, 3]);
+    vec.insert_vec(3, vec![4]);
+    vec.insert_vec(6, vec![7]);
+    let mut cursor = vec.cursor(0);
+    assert_eq!(
+        cursor.by_ref().map(|o| o.copied()).collect::<Vec<_>>(),
+        vec.iter().map(|o| o.copied()).collect::<Vec<_>>()
+    );
+    assert_eq!(cursor.position(), 8);
+    let mut backwards = vec![];
+    while let Some(item) = cursor.prev() {
+        backwards.push(item.copied());
+    }
+    assert_eq!(
+        backwards,
+        vec![None, Some(7), None, None, Some(4), Some(3), Some(2), None]
+    );
+}
+
+#[test]
+fn seek_and_skip_to_loaded() {
+    let mut vec = SparseVec::with_len(20);
+    vec.insert_vec(2, vec![3, 4]);
+    vec.insert_vec(10, vec![11]);
+    let mut cursor = vec.cursor(15);
+    assert_eq!(cursor.get(), None);
+    cursor.seek(3);
+    assert_eq!(cursor.get(), Some(&4));
+    assert_eq!(cursor.skip_to_next_loaded(), Some(3));
+    cursor.seek(4);
+    assert_eq!(cursor.skip_to_next_loaded(), Some(10));
+    assert_eq!(cursor.get(), Some(&11));
+    cursor.seek(11);
+    assert_eq!(cursor.skip_to_next_loaded(), None);
+    assert_eq!(cursor.position(), 20);
+    cursor.seek(100);
+    assert_eq!(cursor.position(), 20);
+    assert_eq!(cursor.next(), None);
+}
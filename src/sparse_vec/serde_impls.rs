@@ -0,0 +1,97 @@
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use super::SparseVec;
+
+/// Serialized form of a SparseVec, the length and the loaded blocks in order
+#[derive(Serialize)]
+struct BlocksRef<'s, T> {
+    len: usize,
+    unknown_len: bool,
+    blocks: &'s [(usize, Vec<T>)],
+}
+
+#[derive(Deserialize)]
+struct Blocks<T> {
+    len: usize,
+    #[serde(default)]
+    unknown_len: bool,
+    blocks: Vec<(usize, Vec<T>)>,
+}
+
+impl<T: Serialize> Serialize for SparseVec<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        BlocksRef {
+            len: self.len,
+            unknown_len: !self.len_known,
+            blocks: &self.blocks[..],
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for SparseVec<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let Blocks {
+            len,
+            unknown_len,
+            blocks,
+        } = Blocks::deserialize(deserializer)?;
+        let mut vec = SparseVec::from_blocks(len, blocks).map_err(de::Error::custom)?;
+        vec.len_known = !unknown_len;
+        Ok(vec)
+    }
+}
+
+#[test]
+fn round_trip() {
+    let mut vec: SparseVec<u8> = SparseVec::with_len(10);
+    vec.insert_vec(1, vec![2, 3]);
+    vec.insert_vec(6, vec![7]);
+    let json = serde_json::to_string(&vec).unwrap();
+    assert_eq!(
+        json,
+        r#"{"len":10,"unknown_len":false,"blocks":[[1,[2,3]],[6,[7]]]}"#
+    );
+    let restored: SparseVec<u8> = serde_json::from_str(&json).unwrap();
+    assert_eq!(
+        restored.iter().map(|o| o.copied()).collect::<Vec<_>>(),
+        vec.iter().map(|o| o.copied()).collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn read_without_unknown_len() {
+    let vec: SparseVec<u8> = serde_json::from_str(r#"{"len":3,"blocks":[[0,[1]]]}"#).unwrap();
+    assert_eq!(vec.known_len(), Some(3));
+}
+
+#[test]
+fn bincode_round_trip() {
+    let mut vec: SparseVec<u8> = SparseVec::with_len(10);
+    vec.insert_vec(1, vec![2, 3]);
+    let restored: SparseVec<u8> = bincode::deserialize(&bincode::serialize(&vec).unwrap()).unwrap();
+    assert_eq!(restored, vec);
+    let mut vec: SparseVec<u8> = SparseVec::with_unknown_len();
+    vec.insert_vec(4, vec![5]);
+    let restored: SparseVec<u8> = bincode::deserialize(&bincode::serialize(&vec).unwrap()).unwrap();
+    assert_eq!(restored, vec);
+    assert_eq!(restored.known_len(), None);
+}
+
+#[test]
+fn reject_invalid_blocks() {
+    assert!(serde_json::from_str::<SparseVec<u8>>(r#"{"len":3,"blocks":[[2,[3,4]]]}"#).is_err());
+    assert!(
+        serde_json::from_str::<SparseVec<u8>>(r#"{"len":5,"blocks":[[0,[1,2]],[1,[2]]]}"#).is_err()
+    );
+}
+
+#[test]
+fn round_trip_unknown_len() {
+    let mut vec: SparseVec<u8> = SparseVec::with_unknown_len();
+    vec.insert_vec(1, vec![2]);
+    let json = serde_json::to_string(&vec).unwrap();
+    assert_eq!(json, r#"{"len":2,"unknown_len":true,"blocks":[[1,[2]]]}"#);
+    let restored: SparseVec<u8> = serde_json::from_str(&json).unwrap();
+    assert_eq!(restored, vec);
+}
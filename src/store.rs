@@ -0,0 +1,317 @@
+use std::{marker::PhantomData, ops::Range};
+
+use crate::{
+    planner::Coverage,
+    sparse_vec::{clamp_range, InsertError},
+};
+
+/// Storage for the blocks of a StoredSparseVec, keyed by the offset of each block
+/// The StoredSparseVec keeps track of which blocks are stored, so a store is only asked for blocks it holds and is never given overlapping blocks
+pub trait BlockStore<T> {
+    /// Store a block of items starting at `offset`
+    fn insert_block(&mut self, offset: usize, block: Vec<T>);
+
+    /// The stored block starting at `offset`, this takes `&mut self` so that a store can load it back from elsewhere
+    fn get_block(&mut self, offset: usize) -> Option<&[T]>;
+
+    /// Remove and return the stored block starting at `offset`
+    fn evict_block(&mut self, offset: usize) -> Option<Vec<T>>;
+}
+
+/// In memory storage of blocks in a Vec sorted by offset, the same layout that SparseVec uses
+#[derive(Debug, Clone)]
+pub struct VecStore<T> {
+    blocks: Vec<(usize, Vec<T>)>,
+}
+
+impl<T> Default for VecStore<T> {
+    fn default() -> Self {
+        VecStore { blocks: vec![] }
+    }
+}
+
+impl<T> BlockStore<T> for VecStore<T> {
+    fn insert_block(&mut self, offset: usize, block: Vec<T>) {
+        let pos = self.blocks.partition_point(|(o, _)| *o < offset);
+        self.blocks.insert(pos, (offset, block));
+    }
+
+    fn get_block(&mut self, offset: usize) -> Option<&[T]> {
+        let pos = self
+            .blocks
+            .binary_search_by_key(&offset, |(o, _)| *o)
+            .ok()?;
+        Some(&self.blocks[pos].1)
+    }
+
+    fn evict_block(&mut self, offset: usize) -> Option<Vec<T>> {
+        let pos = self
+            .blocks
+            .binary_search_by_key(&offset, |(o, _)| *o)
+            .ok()?;
+        Some(self.blocks.remove(pos).1)
+    }
+}
+
+/// A sparse vec which keeps the blocks in a pluggable BlockStore, while tracking the loaded ranges itself so that requests can be planned over it without touching the store
+#[derive(Debug, Clone)]
+pub struct StoredSparseVec<T, S = VecStore<T>> {
+    len: usize,
+    /// The range of each stored block, sorted and not overlapping
+    blocks: Vec<Range<usize>>,
+    store: S,
+    item: PhantomData<T>,
+}
+
+impl<T> StoredSparseVec<T> {
+    pub fn with_len(len: usize) -> Self {
+        Self::with_store(len, VecStore::default())
+    }
+}
+
+impl<T, S: BlockStore<T>> StoredSparseVec<T, S> {
+    /// Construct with an empty store
+    pub fn with_store(len: usize, store: S) -> Self {
+        StoredSparseVec {
+            len,
+            blocks: vec![],
+            store,
+            item: PhantomData,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    /// Whether the item at `idx` is loaded
+    pub fn contains(&self, idx: usize) -> bool {
+        self.block_containing(idx).is_some()
+    }
+
+    /// Get the item at `idx` if it is loaded
+    pub fn get(&mut self, idx: usize) -> Option<&T> {
+        let (range, block) = self.get_block_containing(idx)?;
+        block.get(idx - range.start)
+    }
+
+    /// Get the block which holds `idx` and the range it occupies
+    pub fn get_block_containing(&mut self, idx: usize) -> Option<(Range<usize>, &[T])> {
+        let range = self.blocks[self.block_containing(idx)?].clone();
+        let block = self.store.get_block(range.start)?;
+        Some((range, block))
+    }
+
+    /// The ranges within `idxs` which are not loaded, clamped to the length
+    pub fn gaps_in(&self, idxs: Range<usize>) -> impl Iterator<Item = Range<usize>> + '_ {
+        let idxs = clamp_range(self.len, idxs);
+        let first = self.blocks.partition_point(|block| block.end <= idxs.start);
+        let mut position = idxs.start;
+        self.blocks[first..]
+            .iter()
+            .cloned()
+            .chain(Some(idxs.end..idxs.end))
+            .filter_map(move |block| {
+                let gap = position..block.start.min(idxs.end);
+                position = position.max(block.end);
+                Some(gap).filter(|gap| !gap.is_empty())
+            })
+    }
+
+    /// The contiguous ranges which are loaded, adjacent blocks are reported as one range
+    pub fn loaded_ranges(&self) -> Vec<Range<usize>> {
+        let mut ranges: Vec<Range<usize>> = Vec::with_capacity(self.blocks.len());
+        for block in &self.blocks {
+            match ranges.last_mut() {
+                Some(range) if range.end == block.start => range.end = block.end,
+                _ => ranges.push(block.clone()),
+            }
+        }
+        ranges
+    }
+
+    /// Whether every item in the range is loaded, ranges extending past the end are never loaded
+    pub fn is_range_loaded(&self, idxs: Range<usize>) -> bool {
+        idxs.end <= self.len && self.gaps_in(idxs).next().is_none()
+    }
+
+    /// Insert data into empty space
+    // Panics if space is occupied or the data extends past the end
+    pub fn insert_vec(&mut self, start: usize, vec: Vec<T>) {
+        if let Err(err) = self.try_insert_vec(start, vec) {
+            panic!("{}", err);
+        }
+    }
+
+    /// Insert data into empty space, or leave the StoredSparseVec unchanged if the space is occupied or the data extends past the end
+    pub fn try_insert_vec(&mut self, start: usize, vec: Vec<T>) -> Result<(), InsertError> {
+        let end = start
+            .checked_add(vec.len())
+            .filter(|end| *end <= self.len)
+            .ok_or(InsertError::OutOfBounds {
+                start,
+                len: vec.len(),
+            })?;
+        if vec.is_empty() {
+            return Ok(());
+        }
+        let pos = self.blocks.partition_point(|block| block.end <= start);
+        if let Some(block) = self.blocks.get(pos).filter(|block| block.start < end) {
+            return Err(InsertError::Overlap(block.clone()));
+        }
+        self.blocks.insert(pos, start..end);
+        self.store.insert_block(start, vec);
+        Ok(())
+    }
+
+    /// Evict any loaded data in the range, splitting blocks which straddle its ends
+    pub fn remove_range(&mut self, range: Range<usize>) {
+        if range.is_empty() {
+            return;
+        }
+        let first = self
+            .blocks
+            .partition_point(|block| block.end <= range.start);
+        let last = self.blocks.partition_point(|block| block.start < range.end);
+        let removed: Vec<_> = self.blocks.drain(first..last.max(first)).collect();
+        for block in removed {
+            let mut vec = match self.store.evict_block(block.start) {
+                Some(vec) => vec,
+                None => continue,
+            };
+            if range.end < block.end {
+                let tail = vec.split_off(range.end - block.start);
+                self.insert_vec(range.end, tail);
+            }
+            if block.start < range.start {
+                vec.truncate(range.start - block.start);
+                self.insert_vec(block.start, vec);
+            }
+        }
+    }
+
+    /// Evict all loaded data, keeping the length
+    pub fn clear(&mut self) {
+        self.remove_range(0..self.len);
+    }
+
+    pub fn into_store(self) -> S {
+        self.store
+    }
+
+    fn block_containing(&self, idx: usize) -> Option<usize> {
+        let pos = self.blocks.partition_point(|block| block.end <= idx);
+        self.blocks
+            .get(pos)
+            .filter(|block| block.start <= idx)
+            .map(|_| pos)
+    }
+}
+
+impl<T, S: BlockStore<T>> Coverage for StoredSparseVec<T, S> {
+    fn known_len(&self) -> Option<usize> {
+        Some(self.len)
+    }
+
+    fn loaded_end(&self) -> usize {
+        self.len
+    }
+
+    fn unloaded_in(&self, idxs: Range<usize>) -> Vec<Range<usize>> {
+        let loaded_end = idxs.end.min(self.len);
+        let mut gaps: Vec<_> = self.gaps_in(idxs.start..loaded_end).collect();
+        let past_end = idxs.start.max(loaded_end)..idxs.end;
+        if !past_end.is_empty() {
+            match gaps.last_mut() {
+                Some(last) if last.end == past_end.start => last.end = past_end.end,
+                _ => gaps.push(past_end),
+            }
+        }
+        gaps
+    }
+}
+
+#[test]
+fn insert_get_and_remove() {
+    let mut vec = StoredSparseVec::with_len(10);
+    vec.insert_vec(2, vec![3, 4, 5]);
+    vec.insert_vec(5, vec![6]);
+    assert_eq!(vec.get(3), Some(&4));
+    assert_eq!(vec.get(6), None);
+    assert_eq!(
+        vec.try_insert_vec(1, vec![0, 0]),
+        Err(InsertError::Overlap(2..5))
+    );
+    assert_eq!(
+        vec.try_insert_vec(9, vec![0, 0]),
+        Err(InsertError::OutOfBounds { start: 9, len: 2 })
+    );
+    assert_eq!(vec.loaded_ranges(), vec![2..6]);
+    vec.remove_range(3..4);
+    assert_eq!(vec.loaded_ranges(), vec![2..3, 4..6]);
+    assert_eq!(
+        vec.gaps_in(0..10).collect::<Vec<_>>(),
+        vec![0..2, 3..4, 6..10]
+    );
+    assert_eq!(vec.get(4), Some(&5));
+    assert!(vec.is_range_loaded(4..6));
+    vec.clear();
+    assert_eq!(vec.gaps_in(0..10).collect::<Vec<_>>(), vec![0..10]);
+}
+
+#[test]
+fn custom_store() {
+    use std::collections::HashMap;
+
+    #[derive(Default)]
+    struct MapStore(HashMap<usize, Vec<u8>>);
+
+    impl BlockStore<u8> for MapStore {
+        fn insert_block(&mut self, offset: usize, block: Vec<u8>) {
+            self.0.insert(offset, block);
+        }
+
+        fn get_block(&mut self, offset: usize) -> Option<&[u8]> {
+            self.0.get(&offset).map(|block| &block[..])
+        }
+
+        fn evict_block(&mut self, offset: usize) -> Option<Vec<u8>> {
+            self.0.remove(&offset)
+        }
+    }
+
+    let mut vec = StoredSparseVec::with_store(10, MapStore::default());
+    vec.insert_vec(0, vec![1, 2, 3]);
+    vec.remove_range(1..2);
+    assert_eq!(vec.get(2), Some(&3));
+    assert_eq!(vec.store().0.len(), 2);
+}
+
+#[test]
+fn plan_over_stored_vec() {
+    use crate::{
+        in_flight::InFlight,
+        planner::{next_request_for_view_with, next_requests_for_view, PrefetchConfig},
+    };
+
+    let mut vec = StoredSparseVec::with_len(100);
+    vec.insert_vec(40, vec![0u8; 10]);
+    assert_eq!(
+        next_requests_for_view(&vec, 40..60, &PrefetchConfig::default()),
+        vec![50..70, 30..40]
+    );
+    let mut in_flight = InFlight::new();
+    in_flight.start(50..70);
+    assert_eq!(
+        next_request_for_view_with(&in_flight.over(&vec), 40..60, &PrefetchConfig::default()),
+        Some(30..40)
+    );
+}
@@ -0,0 +1,151 @@
+//! Proptest strategies for SparseVecs, for testing code built on them
+use std::{fmt::Debug, ops::Range};
+
+use proptest::{collection, option, prelude::*};
+
+use crate::sparse_vec::SparseVec;
+
+/// An operation which can be applied to a SparseVec and to a `Vec<Option<T>>` model of it
+#[derive(Debug, Clone)]
+pub enum Op<T> {
+    Insert(usize, Vec<T>),
+    Remove(Range<usize>),
+}
+
+impl<T: Clone> Op<T> {
+    /// Apply to the SparseVec, ignoring an insert which is rejected
+    pub fn apply(&self, vec: &mut SparseVec<T>) {
+        match self {
+            Op::Insert(start, items) => {
+                let _ = vec.try_insert_vec(*start, items.clone());
+            }
+            Op::Remove(range) => vec.remove_range(range.clone()),
+        }
+    }
+
+    /// Apply to the model, ignoring an insert which a SparseVec would reject
+    pub fn apply_model(&self, model: &mut [Option<T>]) {
+        match self {
+            Op::Insert(start, items) => {
+                if let Some(slots) = model.get_mut(*start..(start + items.len())) {
+                    if slots.iter().all(Option::is_none) {
+                        for (slot, item) in slots.iter_mut().zip(items) {
+                            *slot = Some(item.clone());
+                        }
+                    }
+                }
+            }
+            Op::Remove(range) => {
+                let range = range.start.min(model.len())..range.end.min(model.len());
+                for slot in &mut model[range] {
+                    *slot = None;
+                }
+            }
+        }
+    }
+}
+
+/// A `Vec<Option<T>>` model along with a SparseVec holding the same items, with loaded runs split into adjacent blocks at random
+pub fn model_and_sparse_vec<S>(
+    items: S,
+    max_len: usize,
+) -> impl Strategy<Value = (Vec<Option<S::Value>>, SparseVec<S::Value>)>
+where
+    S: Strategy,
+    S::Value: Clone + Debug,
+{
+    collection::vec((option::of(items), any::<bool>()), 0..=max_len).prop_map(|slots| {
+        let mut vec = SparseVec::with_len(slots.len());
+        let mut block: Option<(usize, Vec<S::Value>)> = None;
+        for (idx, (item, split)) in slots.iter().enumerate() {
+            match item {
+                Some(item) => match &mut block {
+                    Some((_, items)) if !split => items.push(item.clone()),
+                    _ => {
+                        if let Some((start, items)) = block.replace((idx, vec![item.clone()])) {
+                            vec.insert_vec(start, items);
+                        }
+                    }
+                },
+                None => {
+                    if let Some((start, items)) = block.take() {
+                        vec.insert_vec(start, items);
+                    }
+                }
+            }
+        }
+        if let Some((start, items)) = block {
+            vec.insert_vec(start, items);
+        }
+        let model = slots.into_iter().map(|(item, _)| item).collect();
+        (model, vec)
+    })
+}
+
+/// A SparseVec of up to `max_len` items with loaded runs split into adjacent blocks at random
+pub fn sparse_vec<S>(items: S, max_len: usize) -> impl Strategy<Value = SparseVec<S::Value>>
+where
+    S: Strategy,
+    S::Value: Clone + Debug,
+{
+    model_and_sparse_vec(items, max_len).prop_map(|(_, vec)| vec)
+}
+
+/// A range within `0..=len`, which may be empty
+pub fn range_within(len: usize) -> impl Strategy<Value = Range<usize>> {
+    (0..=len, 0..=len).prop_map(|(a, b)| a.min(b)..a.max(b))
+}
+
+/// Operations on a SparseVec of length `len`, inserts may overlap or extend past the end
+pub fn ops<S>(items: S, len: usize, max_ops: usize) -> impl Strategy<Value = Vec<Op<S::Value>>>
+where
+    S: Strategy + Clone,
+    S::Value: Clone + Debug,
+{
+    let op = prop_oneof![
+        (0..=len, collection::vec(items, 0..=(len / 2 + 1)))
+            .prop_map(|(start, items)| Op::Insert(start, items)),
+        range_within(len + 2).prop_map(Op::Remove),
+    ];
+    collection::vec(op, 0..=max_ops)
+}
+
+#[cfg(test)]
+proptest! {
+    #[test]
+    fn iteration_matches_model((model, vec) in model_and_sparse_vec(any::<u8>(), 64)) {
+        prop_assert_eq!(vec.iter().map(|o| o.copied()).collect::<Vec<_>>(), model);
+    }
+
+    #[test]
+    fn ops_match_model_and_never_overlap(
+        (len, ops) in (0..64usize).prop_flat_map(|len| (Just(len), ops(any::<u8>(), len, 20)))
+    ) {
+        let mut vec = SparseVec::with_len(len);
+        let mut model = vec![None; len];
+        for op in ops {
+            op.apply(&mut vec);
+            op.apply_model(&mut model);
+            prop_assert_eq!(vec.iter().map(|o| o.copied()).collect::<Vec<_>>(), model.clone());
+            let blocks: Vec<_> = vec.iter_blocks().map(|(range, _)| range).collect();
+            for pair in blocks.windows(2) {
+                prop_assert!(pair[0].end <= pair[1].start);
+            }
+        }
+    }
+
+    #[test]
+    fn request_is_unloaded_and_within_bounds(
+        (vec, view) in sparse_vec(any::<u8>(), 64)
+            .prop_flat_map(|vec| {
+                let len = vec.len();
+                (Just(vec), range_within(len))
+            })
+    ) {
+        if let Some(request) = crate::next_request_for_view(&vec, view) {
+            prop_assert!(!request.is_empty());
+            prop_assert!(request.end <= vec.len());
+            prop_assert!(vec.iter_range(request).all(|item| item.is_none()));
+        }
+    }
+}
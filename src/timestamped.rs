@@ -0,0 +1,172 @@
+use std::{
+    ops::{Deref, Range},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    lru::forget_range,
+    sparse_vec::{complement, InsertError, SparseVec},
+};
+
+/// A SparseVec which records when each block was inserted, so that old data can be refreshed while it is still shown
+#[derive(Debug)]
+pub struct TimestampedSparseVec<T> {
+    vec: SparseVec<T>,
+    /// The range of each inserted block and when it was inserted
+    inserted: Vec<(Range<usize>, Instant)>,
+}
+
+impl<T> TimestampedSparseVec<T> {
+    pub fn with_len(len: usize) -> Self {
+        Self::new(SparseVec::with_len(len), Instant::now())
+    }
+
+    /// Wrap an existing SparseVec, its loaded blocks are treated as inserted at `inserted`
+    pub fn new(vec: SparseVec<T>, inserted: Instant) -> Self {
+        let inserted = vec
+            .iter_blocks()
+            .map(|(range, _)| (range, inserted))
+            .collect();
+        TimestampedSparseVec { vec, inserted }
+    }
+
+    /// Insert data into empty space, timestamped now
+    // Panics if space is occupied or the data extends past the end
+    pub fn insert_vec(&mut self, start: usize, vec: Vec<T>) {
+        if let Err(err) = self.try_insert_vec(start, vec) {
+            panic!("{}", err);
+        }
+    }
+
+    /// Insert data into empty space timestamped now, or leave the TimestampedSparseVec unchanged if the space is occupied or the data extends past the end
+    pub fn try_insert_vec(&mut self, start: usize, vec: Vec<T>) -> Result<(), InsertError> {
+        self.try_insert_vec_at(start, vec, Instant::now())
+    }
+
+    /// Insert data into empty space with the time it was fetched, or leave the TimestampedSparseVec unchanged if the space is occupied or the data extends past the end
+    pub fn try_insert_vec_at(
+        &mut self,
+        start: usize,
+        vec: Vec<T>,
+        inserted: Instant,
+    ) -> Result<(), InsertError> {
+        let range = start..(start.saturating_add(vec.len()));
+        self.vec.try_insert_vec(start, vec)?;
+        if !range.is_empty() {
+            self.inserted.push((range, inserted));
+        }
+        Ok(())
+    }
+
+    /// Insert data timestamped now, replacing any which is already loaded in its range, use this for refreshed data
+    // Panics if the data extends past the end
+    pub fn insert_vec_overwrite(&mut self, start: usize, vec: Vec<T>) {
+        let range = start..(start.saturating_add(vec.len()));
+        self.vec.insert_vec_overwrite(start, vec);
+        forget_range(&mut self.inserted, range.clone());
+        if !range.is_empty() {
+            self.inserted.push((range, Instant::now()));
+        }
+    }
+
+    /// When the item at `idx` was inserted, if it is loaded
+    pub fn inserted_at(&self, idx: usize) -> Option<Instant> {
+        self.inserted
+            .iter()
+            .find(|(range, _)| range.contains(&idx))
+            .map(|(_, inserted)| *inserted)
+    }
+
+    /// The loaded ranges which were inserted more than `older_than` ago, and should be requested again
+    pub fn stale_ranges(&self, older_than: Duration) -> Vec<Range<usize>> {
+        match Instant::now().checked_sub(older_than) {
+            Some(cutoff) => self.inserted_before(cutoff),
+            None => vec![],
+        }
+    }
+
+    /// The loaded ranges which were inserted before `cutoff`, adjacent blocks are reported as one range
+    pub fn inserted_before(&self, cutoff: Instant) -> Vec<Range<usize>> {
+        let mut stale: Vec<_> = self
+            .inserted
+            .iter()
+            .filter(|(_, inserted)| *inserted < cutoff)
+            .map(|(range, _)| range.clone())
+            .collect();
+        stale.sort_unstable_by_key(|range| range.start);
+        let mut merged: Vec<Range<usize>> = Vec::with_capacity(stale.len());
+        for range in stale {
+            match merged.last_mut() {
+                Some(last) if last.end == range.start => last.end = range.end,
+                _ => merged.push(range),
+            }
+        }
+        merged
+    }
+
+    /// Evict any loaded data in the range
+    pub fn remove_range(&mut self, idxs: Range<usize>) {
+        self.vec.remove_range(idxs.clone());
+        forget_range(&mut self.inserted, idxs);
+    }
+
+    /// Evict all loaded data outside the range
+    pub fn evict_outside(&mut self, keep: Range<usize>) {
+        self.remove_range(0..keep.start);
+        self.remove_range(keep.end..self.vec.len());
+    }
+
+    /// Evict all loaded data outside of every one of the ranges
+    pub fn evict_outside_ranges(&mut self, keep: &[Range<usize>]) {
+        for range in complement(keep, self.vec.len()) {
+            self.remove_range(range);
+        }
+    }
+
+    /// Evict all loaded data
+    pub fn clear(&mut self) {
+        self.vec.clear();
+        self.inserted.clear();
+    }
+
+    pub fn into_inner(self) -> SparseVec<T> {
+        self.vec
+    }
+}
+
+impl<T> Deref for TimestampedSparseVec<T> {
+    type Target = SparseVec<T>;
+
+    fn deref(&self) -> &SparseVec<T> {
+        &self.vec
+    }
+}
+
+#[test]
+fn ranges_inserted_before_cutoff() {
+    let start = Instant::now();
+    let later = start + Duration::from_secs(10);
+    let mut vec = TimestampedSparseVec::with_len(100);
+    vec.try_insert_vec_at(0, vec![0; 10], start).unwrap();
+    vec.try_insert_vec_at(10, vec![0; 10], start).unwrap();
+    vec.try_insert_vec_at(20, vec![0; 10], later).unwrap();
+    vec.try_insert_vec_at(50, vec![0; 10], start).unwrap();
+    vec.remove_range(5..6);
+    assert_eq!(
+        vec.inserted_before(start + Duration::from_secs(5)),
+        vec![0..5, 6..20, 50..60]
+    );
+    assert_eq!(vec.inserted_at(25), Some(later));
+    assert_eq!(vec.inserted_at(5), None);
+}
+
+#[test]
+fn refresh_replaces_timestamp() {
+    let mut vec = TimestampedSparseVec::with_len(100);
+    let old = Instant::now() - Duration::from_secs(60);
+    vec.try_insert_vec_at(0, vec![0; 10], old).unwrap();
+    assert_eq!(vec.stale_ranges(Duration::from_secs(30)), vec![0..10]);
+    vec.insert_vec_overwrite(2, vec![1; 3]);
+    assert_eq!(vec.stale_ranges(Duration::from_secs(30)), vec![0..2, 5..10]);
+    assert_eq!(vec[3], 1);
+}